@@ -46,3 +46,86 @@ fn benchmark_tree(b: &mut test::Bencher) {
         router.route("/api/v1/user/110/profile");
     });
 }
+
+#[bench]
+fn benchmark_tree_deep_params(b: &mut test::Bencher) {
+    let mut router = TreeRouter::new();
+
+    router.add("/a/:b/c/:d/e/:f/g/:h/i/:j", "deep");
+    router.add("/a/:b/c/:d/e/:f/g/:h/*k", "deep_wildcard");
+
+    b.iter(|| {
+        router.route("/a/1/c/2/e/3/g/4/i/5");
+        router.route("/a/1/c/2/e/3/g/4/x/y/z");
+    });
+}
+
+#[bench]
+fn benchmark_nfa_param_branches(b: &mut test::Bencher) {
+    let mut router = Router::new();
+
+    router.add("/:a/:b/:c/:d/:e/:f", "params");
+    router.add("/:a/:b/:c/:d/:e/f", "static_f");
+    router.add("/:a/:b/:c/:d/e/:f", "static_e");
+    router.add("/:a/:b/:c/d/:e/:f", "static_d");
+    router.add("/:a/:b/c/:d/:e/:f", "static_c");
+    router.add("/:a/b/*rest", "wildcard");
+
+    b.iter(|| {
+        router.route("/a/b/c/d/e/f");
+        router.route("/1/2/3/4/5/6");
+        router.route("/a/b/x/y/z/w/v");
+    });
+}
+
+#[bench]
+fn benchmark_tree_static(b: &mut test::Bencher) {
+    let mut router = TreeRouter::new();
+
+    router.add("/api/v1/self/profile", "profile");
+    router.add("/api/v1/self/settings", "settings");
+    router.add("/api/v1/:user/profile", "user_profile");
+    router.add("/api/v1/*v1", "v1");
+
+    b.iter(|| {
+        router.route("/api/v1/self/profile");
+        router.route("/api/v1/self/settings");
+    });
+}
+
+#[bench]
+fn benchmark_nfa_all_static(b: &mut test::Bencher) {
+    let mut router = Router::new();
+
+    router.add("/api/v1/self/profile", "profile");
+    router.add("/api/v1/self/settings", "settings");
+    router.add("/api/v1/users", "users");
+    router.add("/api/v1/posts", "posts");
+
+    b.iter(|| {
+        router.route("/api/v1/self/profile");
+        router.route("/api/v1/self/settings");
+    });
+}
+
+#[bench]
+fn benchmark_tree_frozen(b: &mut test::Bencher) {
+    let mut router = TreeRouter::new();
+
+    router.add("/posts", "posts");
+    router.add("/posts/:post_id/comments/:id", "comment");
+    router.add("/posts/:post_id/comments", "comments");
+    router.add("/posts/:post_id", "post");
+    router.add("/comments", "comments2");
+    router.add("/comments/:id", "comment2");
+    router.add("/api/v1/self/profile", "profile");
+    router.add("/api/v1/*v1", "v1");
+    let router = router.freeze();
+
+    b.iter(|| {
+        router.route("/posts");
+        router.route("/posts/100/comments/200");
+        router.route("/api/v1/self/profile");
+        router.route("/api/v1/user/110/profile");
+    });
+}
@@ -0,0 +1,179 @@
+use std::{collections::BTreeSet, fmt};
+
+const CHAR_OPEN: char = '(';
+const CHAR_CLOSE: char = ')';
+const CHAR_KIND_OPEN: char = '<';
+const CHAR_KIND_CLOSE: char = '>';
+
+/// Segments which params never capture, shared by both routers so they agree.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Reserved {
+    words: BTreeSet<String>,
+}
+
+impl Reserved {
+    pub(crate) fn new<S: ToString>(words: impl IntoIterator<Item = S>) -> Self {
+        Reserved {
+            words: words.into_iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    /// Whether the segment is reserved, ignoring ASCII case like static segments do
+    /// when `case_insensitive` is set.
+    pub(crate) fn contains(&self, seg: &str, case_insensitive: bool) -> bool {
+        if case_insensitive {
+            self.words.iter().any(|w| w.eq_ignore_ascii_case(seg))
+        } else {
+            self.words.contains(seg)
+        }
+    }
+}
+
+/// Constraint of a param, either a built-in kind like `int` of `:id<int>`,
+/// or an inline regex like `\d+` of `:id(\d+)` with the `regex` feature.
+#[derive(Clone)]
+pub(crate) enum Constraint {
+    Kind(Kind),
+    #[cfg(feature = "regex")]
+    Regex(String, regex::Regex),
+}
+
+/// Built-in segment matchers, which need no extra dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// Decimal integer, optionally negative.
+    Int,
+    /// Hyphenated UUID, as `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    Uuid,
+    /// ASCII letters.
+    Alpha,
+}
+
+impl Kind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(Kind::Int),
+            "uuid" => Some(Kind::Uuid),
+            "alpha" => Some(Kind::Alpha),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Int => "int",
+            Kind::Uuid => "uuid",
+            Kind::Alpha => "alpha",
+        }
+    }
+
+    fn is_match(&self, seg: &str) -> bool {
+        match self {
+            Kind::Int => {
+                let digits = seg.strip_prefix('-').unwrap_or(seg);
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            }
+            Kind::Uuid => {
+                let groups: Vec<&str> = seg.split('-').collect();
+                groups.len() == 5
+                    && groups
+                        .iter()
+                        .zip([8, 4, 4, 4, 12])
+                        .all(|(g, len)| g.len() == len && g.bytes().all(|b| b.is_ascii_hexdigit()))
+            }
+            Kind::Alpha => !seg.is_empty() && seg.bytes().all(|b| b.is_ascii_alphabetic()),
+        }
+    }
+}
+
+impl Constraint {
+    #[cfg(feature = "regex")]
+    fn regex(source: &str) -> Result<Self, String> {
+        let regex = regex::Regex::new(&format!("^(?:{source})$"))
+            .map_err(|e| format!("invalid param constraint `{source}`: {e}"))?;
+
+        Ok(Constraint::Regex(source.to_owned(), regex))
+    }
+
+    /// Split the param into its name and optional constraint,
+    /// panics when the constraint is invalid.
+    pub(crate) fn parse(param: &str) -> (&str, Option<Self>) {
+        Self::try_parse(param).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Split the param into its name and optional constraint,
+    /// regex constraints are only recognized with the `regex` feature.
+    pub(crate) fn try_parse(param: &str) -> Result<(&str, Option<Self>), String> {
+        if let Some((name, kind)) = Self::split(param, CHAR_KIND_OPEN, CHAR_KIND_CLOSE) {
+            return match Kind::from_name(kind) {
+                Some(kind) => Ok((name, Some(Constraint::Kind(kind)))),
+                None => Err(format!("unknown param kind `{kind}`")),
+            };
+        }
+
+        #[cfg(feature = "regex")]
+        if let Some((name, source)) = Self::split(param, CHAR_OPEN, CHAR_CLOSE) {
+            return Ok((name, Some(Constraint::regex(source)?)));
+        }
+
+        Ok((param, None))
+    }
+
+    /// Name of the param, without the constraint.
+    pub(crate) fn name(param: &str) -> &str {
+        if let Some((name, _)) = Self::split(param, CHAR_KIND_OPEN, CHAR_KIND_CLOSE) {
+            return name;
+        }
+
+        if cfg!(feature = "regex") {
+            Self::split(param, CHAR_OPEN, CHAR_CLOSE).map_or(param, |(name, _)| name)
+        } else {
+            param
+        }
+    }
+
+    fn split(param: &str, open: char, close: char) -> Option<(&str, &str)> {
+        let (name, rest) = param.split_once(open)?;
+        let source = rest.strip_suffix(close)?;
+
+        Some((name, source))
+    }
+
+    pub(crate) fn is_match(&self, seg: &str) -> bool {
+        match self {
+            Constraint::Kind(kind) => kind.is_match(seg),
+            #[cfg(feature = "regex")]
+            Constraint::Regex(_, regex) => regex.is_match(seg),
+        }
+    }
+}
+
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Constraint::Kind(l), Constraint::Kind(r)) => l == r,
+            #[cfg(feature = "regex")]
+            (Constraint::Regex(l, _), Constraint::Regex(r, _)) => l == r,
+            #[cfg(feature = "regex")]
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::Kind(kind) => write!(f, "{CHAR_KIND_OPEN}{}{CHAR_KIND_CLOSE}", kind.name()),
+            #[cfg(feature = "regex")]
+            Constraint::Regex(source, _) => write!(f, "{CHAR_OPEN}{source}{CHAR_CLOSE}"),
+        }
+    }
+}
+
+impl fmt::Debug for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Constraint")
+            .field(&self.to_string())
+            .finish()
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+type Init<T> = Box<dyn FnOnce() -> T + Send>;
+
+/// Endpoint of a route, which may be built on first access.
+pub(crate) struct Endpoint<T>(Inner<T>);
+
+enum Inner<T> {
+    Ready(T),
+    /// Shared by the clones made before the first access, so `init` runs once.
+    Lazy(Arc<Lazy<T>>),
+}
+
+struct Lazy<T> {
+    cell: OnceLock<T>,
+    init: Mutex<Option<Init<T>>>,
+    /// Set when the endpoint is cloned, copies the shared value for `get_mut`.
+    fork: OnceLock<fn(&T) -> T>,
+}
+
+impl<T> Lazy<T> {
+    fn get(&self) -> &T {
+        self.cell.get_or_init(|| {
+            let init = self
+                .init
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take()
+                .expect("endpoint initializer not exist");
+
+            init()
+        })
+    }
+}
+
+impl<T> Endpoint<T> {
+    pub(crate) fn ready(value: T) -> Self {
+        Endpoint(Inner::Ready(value))
+    }
+
+    pub(crate) fn lazy(init: impl FnOnce() -> T + Send + 'static) -> Self {
+        Endpoint(Inner::Lazy(Arc::new(Lazy {
+            cell: OnceLock::new(),
+            init: Mutex::new(Some(Box::new(init))),
+            fork: OnceLock::new(),
+        })))
+    }
+
+    pub(crate) fn get(&self) -> &T {
+        match &self.0 {
+            Inner::Ready(value) => value,
+            Inner::Lazy(lazy) => lazy.get(),
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.force();
+
+        match &mut self.0 {
+            Inner::Ready(value) => value,
+            Inner::Lazy(_) => unreachable!("endpoint not initialized"),
+        }
+    }
+
+    pub(crate) fn into_inner(mut self) -> T {
+        self.force();
+
+        match self.0 {
+            Inner::Ready(value) => value,
+            Inner::Lazy(_) => unreachable!("endpoint not initialized"),
+        }
+    }
+
+    /// Initialize the endpoint and own its value, a shared one is copied.
+    fn force(&mut self) {
+        if let Inner::Lazy(lazy) = &mut self.0 {
+            lazy.get();
+
+            let value = match Arc::get_mut(lazy) {
+                Some(lazy) => lazy.cell.take(),
+                None => lazy.fork.get().map(|fork| fork(lazy.get())),
+            };
+
+            self.0 = Inner::Ready(value.expect("endpoint not initialized"));
+        }
+    }
+}
+
+impl<T: Clone> Clone for Endpoint<T> {
+    fn clone(&self) -> Self {
+        match &self.0 {
+            Inner::Ready(value) => Endpoint::ready(value.clone()),
+            Inner::Lazy(lazy) => match lazy.cell.get() {
+                Some(value) => Endpoint::ready(value.clone()),
+                None => {
+                    lazy.fork.get_or_init(|| T::clone);
+                    Endpoint(Inner::Lazy(lazy.clone()))
+                }
+            },
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Endpoint<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match &self.0 {
+            Inner::Ready(value) => Some(value),
+            Inner::Lazy(lazy) => lazy.cell.get(),
+        };
+
+        match value {
+            Some(value) => f.debug_tuple("Endpoint").field(value).finish(),
+            None => f.write_str("Endpoint(<lazy>)"),
+        }
+    }
+}
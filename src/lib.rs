@@ -24,409 +24,5129 @@
 //! - __segments__: these are of the format `/a/b`.
 //! - __params__: these are of the format `/a/:b`.
 //! - __wildcards__: these are of the format `/a/*b`.
+//!
+//! Params may carry a built-in constraint, one of `int`, `uuid` and `alpha`, as `/a/:b<int>`,
+//! or with the `regex` feature an inline regex, as `/a/:b(\d+)`,
+//! only segments matching the constraint are captured.
+//!
+//! For [`TreeRouter`], the last param may be optional, as `/a/:b?`, which also matches `/a`
+//! with the param absent. Wildcards already match an empty tail, so `*b?` is rejected.
+//!
+//! # Choosing a router
+//!
+//! [`Router`] is backed by an NFA, it tries every candidate route and picks the most
+//! specific one, so a static segment never shadows a param route further down.
+//! It also carries contexts, middlewares, scopes and conflict detection.
+//!
+//! [`TreeRouter`] walks a prefix tree depth first, preferring static over param over
+//! wildcard children at each segment like [`Router`] does, and backtracks to the next
+//! candidate when a later segment misses. A route of more segments ranks above a
+//! wildcard first in both, e.g. `/a/*w` and `/:x/b/c` with the path `/a/b/c` go to
+//! `/:x/b/c`. It routes about twice as fast in `benches/bench.rs`, prefer it for
+//! hot paths.
+//!
+//! Otherwise both capture params, wildcards and leading slashes the same way,
+//! so routes can be moved between them transparently. Only [`TreeRouter`] lets
+//! a wildcard be followed by a suffix, as `/files/*path/download`.
+//! Once all routes are added, [`TreeRouter::freeze`] lays it out in flat arrays
+//! as a [`FrozenRouter`], which routes the same way but can no longer change.
 
 use std::{
-    collections::{btree_map, BTreeMap},
-    ops::Index,
+    collections::{btree_map, BTreeMap, BTreeSet},
+    fmt,
+    ops::{Index, Range},
+    str::FromStr,
+    sync::Arc,
 };
 
+mod constraint;
+mod endpoint;
+mod method;
 mod nfa;
+mod percent;
 mod tree;
 
+use endpoint::Endpoint;
+pub use method::MethodRouter;
+
+/// Identifier of a middleware attached to routes.
+pub type MiddlewareId = usize;
+
 /// Recognizes URL path patterns with support for dynamic and wildcard segments.
+///
+/// Each route may carry an optional context `C`, which is resolved along with the endpoint.
 #[derive(Debug, Clone)]
-pub struct Router<T> {
+pub struct Router<T, C = ()> {
     tree: nfa::Nfa,
-    endpoints: BTreeMap<usize, T>,
+    endpoints: BTreeMap<usize, Endpoint<T>>,
+    contexts: BTreeMap<usize, C>,
+    middlewares: BTreeMap<usize, Vec<MiddlewareId>>,
+    prefix_middlewares: BTreeMap<usize, Vec<MiddlewareId>>,
+    boundaries: BTreeSet<usize>,
+    scopes: Vec<(String, char)>,
+    interner: Interner,
+    decode_params: bool,
+    trailing_slash: TrailingSlash,
 }
 
 impl<T> Router<T> {
     pub fn new() -> Self {
+        Router::with_context()
+    }
+
+    /// Create a router whose segments are separated by `sep` instead of `/`,
+    /// as `com.example.:method` for dotted keys. Wildcard values keep the separator.
+    ///
+    /// Boundaries and prefix middlewares use `sep` as well, while scopes and the
+    /// trailing slash policy still look at `/`. Split dotted wildcard values with
+    /// [`Params::find_segments_by`]. Panics when `sep` is a char reserved for patterns,
+    /// like `:` or `*`.
+    pub fn with_separator(sep: char) -> Self {
+        check_separator(sep);
+
+        let mut router = Router::new();
+        router.tree.set_separator(sep);
+        router
+    }
+
+    /// Create a router with room for `states` NFA states, about one per distinct
+    /// segment of the routes, so adding a large table up front does not reallocate.
+    pub fn with_capacity(states: usize) -> Self {
+        let mut router = Router::new();
+        router.tree.reserve(states);
+        router
+    }
+}
+
+impl<T, C> Router<T, C> {
+    /// Create a router whose routes carry a context of type `C`.
+    pub fn with_context() -> Self {
         Router {
             tree: nfa::Nfa::new(),
             endpoints: BTreeMap::new(),
+            contexts: BTreeMap::new(),
+            middlewares: BTreeMap::new(),
+            prefix_middlewares: BTreeMap::new(),
+            boundaries: BTreeSet::new(),
+            scopes: Vec::new(),
+            interner: Interner::new(),
+            decode_params: false,
+            trailing_slash: TrailingSlash::Strict,
         }
     }
 
-    pub fn add(&mut self, pattern: &str, endpoint: T) {
-        let state = self.tree.insert(pattern);
-        self.endpoints.insert(state, endpoint);
+    /// Percent-decode path segments before matching static segments,
+    /// so `/a%20b` matches a route registered as `/a b`. Params are captured as is.
+    pub fn set_decode_static(&mut self, enable: bool) {
+        self.tree.set_decode_static(enable);
     }
 
-    pub fn route(&self, path: &str) -> Option<(&T, Params)> {
-        self.tree.search(path).map(|found| {
-            let endpoint = self.endpoints.get(&found.state).unwrap();
-            let mut params = Params::new();
+    /// Percent-decode captured param and wildcard values, invalid sequences are left as is.
+    /// Params borrowed by `route_ref` are never decoded.
+    pub fn set_decode_params(&mut self, enable: bool) {
+        self.decode_params = enable;
+    }
 
-            for (n, v) in found.params {
-                if !n.is_empty() {
-                    params.map.insert(n.to_string(), v.to_string());
-                }
-            }
+    /// Match static segments ASCII case-insensitively, captured values keep their case.
+    pub fn case_insensitive(&mut self, enable: bool) {
+        self.tree.set_case_insensitive(enable);
+    }
 
-            (endpoint, params)
-        })
+    /// Reserve segments which params never capture, so they only match static routes.
+    /// Wildcards still take them. With [`Router::case_insensitive`] ASCII case is ignored.
+    pub fn set_reserved_segments(&mut self, words: &[&str]) {
+        self.tree.set_reserved(words);
     }
 
-    pub fn merge(&mut self, path: &str, mut other: Router<T>) {
-        let path = path.trim_end_matches('/');
-        let state = self.tree.locate(path);
+    /// Set how a trailing slash in the routed path is treated.
+    pub fn trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
 
-        let right = other.tree.start_state();
+    fn insert(&mut self, pattern: &str) -> usize {
+        self.interner.intern_pattern(pattern, self.tree.separator());
+        self.tree.insert(pattern)
+    }
 
-        let states = self.tree.merge(state, &other.tree, right);
+    /// Store the endpoint of the pattern, the context of a replaced one is dropped.
+    fn set_endpoint(&mut self, pattern: &str, endpoint: Endpoint<T>) -> usize {
+        let state = self.insert(pattern);
+        self.endpoints.insert(state, endpoint);
+        self.contexts.remove(&state);
+        state
+    }
 
-        for (new, old) in states {
-            if let Some(ep) = other.endpoints.remove(&old) {
-                self.endpoints.insert(new, ep);
+    /// Add the route, overwriting any conflicting one. Like the other `add_*`
+    /// methods the pattern is not validated, see [`Router::try_add`].
+    pub fn add(&mut self, pattern: impl AsRef<str>, endpoint: T) {
+        self.set_endpoint(pattern.as_ref(), Endpoint::ready(endpoint));
+    }
+
+    /// Add the route, fail when the pattern is already registered, when a param
+    /// or wildcard at the same position is named differently, or when the pattern
+    /// or a param constraint is invalid.
+    pub fn try_add(&mut self, pattern: impl AsRef<str>, endpoint: T) -> Result<(), AddError> {
+        let pattern = pattern.as_ref();
+        let sep = self.tree.separator();
+        validate_segments(pattern, sep, false).map_err(AddError::InvalidPattern)?;
+
+        for seg in pattern.split(sep) {
+            if let Some(param) = seg.strip_prefix(':') {
+                constraint::Constraint::try_parse(param).map_err(AddError::InvalidConstraint)?;
             }
         }
-    }
-}
 
-impl<T: Default> Router<T> {
-    pub fn at_or_default(&mut self, path: &str) -> &mut T {
-        let state = self.tree.locate(path);
-        self.tree.accept(state);
+        if let Some(state) = self.tree.find_conflict(pattern) {
+            return Err(AddError::Conflict(RouteConflict {
+                existing: self.tree.pattern(state),
+            }));
+        }
+
+        self.set_endpoint(pattern, Endpoint::ready(endpoint));
 
-        self.endpoints.entry(state).or_default()
+        Ok(())
     }
-}
 
-impl<T: Default> Default for Router<T> {
-    fn default() -> Self {
-        Router::new()
+    /// Add the route, the endpoint is built by `init` on first match.
+    ///
+    /// A clone of the router shares the pending endpoint, `init` still runs once.
+    /// Accessing the endpoint by `get`, `get_mut` or `routes` builds it too.
+    pub fn add_lazy(
+        &mut self,
+        pattern: impl AsRef<str>,
+        init: impl FnOnce() -> T + Send + 'static,
+    ) {
+        self.set_endpoint(pattern.as_ref(), Endpoint::lazy(init));
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct TreeRouter<T> {
-    tree: crate::tree::Tree<T>,
-}
+    pub fn add_with_context(&mut self, pattern: impl AsRef<str>, endpoint: T, context: C) {
+        let state = self.set_endpoint(pattern.as_ref(), Endpoint::ready(endpoint));
+        self.contexts.insert(state, context);
+    }
 
-impl<T> TreeRouter<T> {
-    pub fn new() -> Self {
-        TreeRouter {
-            tree: crate::tree::Tree::new(),
+    /// Remove the route of the pattern, return the removed endpoint.
+    pub fn remove(&mut self, pattern: impl AsRef<str>) -> Option<T> {
+        let state = self.tree.find_state(pattern.as_ref())?;
+
+        if !self.tree.reject(state) {
+            return None;
         }
-    }
 
-    pub fn add(&mut self, pattern: &str, endpoint: T) {
-        self.tree.insert(pattern, endpoint);
+        self.contexts.remove(&state);
+        self.middlewares.remove(&state);
+        self.endpoints.remove(&state).map(Endpoint::into_inner)
     }
 
-    pub fn merge(&mut self, path: &str, other: TreeRouter<T>) {
-        self.tree.merge(path, other.tree);
+    /// Whether an endpoint is registered for the exact pattern, unlike `route`
+    /// the pattern is not matched as a concrete path.
+    pub fn contains(&self, pattern: impl AsRef<str>) -> bool {
+        self.tree
+            .find_state(pattern.as_ref())
+            .is_some_and(|state| self.endpoints.contains_key(&state))
     }
 
-    pub fn route(&self, path: &str) -> Option<(&T, Params)> {
-        self.tree.search(path).map(|(endpoint, p)| {
-            let mut params = Params::new();
-
-            for (_k, (n, v)) in p {
-                params.map.insert(n, v);
-            }
+    /// Get the endpoint stored for the exact pattern, params in the pattern match
+    /// param transitions, not literal segments.
+    pub fn get(&self, pattern: impl AsRef<str>) -> Option<&T> {
+        let state = self.tree.find_state(pattern.as_ref())?;
 
-            (endpoint, params)
-        })
+        self.endpoints.get(&state).map(Endpoint::get)
     }
-}
 
-impl<T: Default> TreeRouter<T> {
-    pub fn at_or_default(&mut self, pattern: &str) -> &mut T {
-        let endpoint = self.tree.at(pattern);
+    /// Get the endpoint stored for the exact pattern mutably.
+    pub fn get_mut(&mut self, pattern: impl AsRef<str>) -> Option<&mut T> {
+        let state = self.tree.find_state(pattern.as_ref())?;
 
-        let data = &mut endpoint.data;
+        self.endpoints.get_mut(&state).map(Endpoint::get_mut)
+    }
 
-        match data {
-            Some(ep) => ep,
-            None => {
-                *data = Some(T::default());
-                data.as_mut().unwrap()
-            }
-        }
+    /// Number of registered routes.
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
     }
-}
 
-impl<T: Default> Default for TreeRouter<T> {
-    fn default() -> Self {
-        TreeRouter::new()
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Params {
-    map: BTreeMap<String, String>,
-}
+    /// Remove all routes, keeping the allocated capacity where possible.
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.endpoints.clear();
+        self.contexts.clear();
+        self.middlewares.clear();
+        self.prefix_middlewares.clear();
+        self.boundaries.clear();
+        self.scopes.clear();
+        self.interner.ids.clear();
+    }
 
-impl Params {
-    pub fn new() -> Self {
-        Params {
-            map: BTreeMap::new(),
-        }
+    /// Iterate all registered routes, with their reconstructed patterns.
+    pub fn routes(&self) -> impl Iterator<Item = (String, &T)> {
+        self.endpoints
+            .iter()
+            .map(|(state, endpoint)| (self.tree.pattern(*state), endpoint.get()))
     }
 
-    pub fn find(&self, key: impl AsRef<str>) -> Option<&str> {
-        self.map.get(key.as_ref()).map(|s| s.as_str())
+    /// Patterns of the registered routes, lazy endpoints are not built.
+    fn patterns(&self) -> impl Iterator<Item = String> + '_ {
+        self.endpoints.keys().map(|state| self.tree.pattern(*state))
     }
 
-    pub fn insert(&mut self, key: impl ToString, value: impl ToString) -> Option<String> {
-        self.map.insert(key.to_string(), value.to_string())
+    /// Size of the compiled NFA, cheap to compute on demand.
+    pub fn stats(&self) -> RouterStats {
+        self.tree.stats()
     }
 
-    pub fn remove(&mut self, key: impl AsRef<str>) -> Option<String> {
-        self.map.remove(key.as_ref())
+    pub fn add_with_middleware(
+        &mut self,
+        pattern: impl AsRef<str>,
+        endpoint: T,
+        middlewares: Vec<MiddlewareId>,
+    ) {
+        let state = self.set_endpoint(pattern.as_ref(), Endpoint::ready(endpoint));
+        self.middlewares
+            .entry(state)
+            .or_default()
+            .extend(middlewares);
     }
 
-    pub fn iter(&self) -> ParamIter {
-        ParamIter(self.map.iter())
+    /// Attach middlewares to every route under `prefix`.
+    pub fn add_prefix_middleware(
+        &mut self,
+        prefix: impl AsRef<str>,
+        middlewares: Vec<MiddlewareId>,
+    ) {
+        let prefix = prefix.as_ref().trim_end_matches(self.tree.separator());
+        let state = if prefix.is_empty() {
+            self.tree.start_state()
+        } else {
+            self.tree.locate(prefix)
+        };
+
+        self.prefix_middlewares
+            .entry(state)
+            .or_default()
+            .extend(middlewares);
     }
-}
 
-impl Default for Params {
-    fn default() -> Self {
-        Params::new()
+    /// Whether more than one route matches the path with equal specificity,
+    /// so the chosen endpoint would be arbitrary.
+    pub fn is_ambiguous(&self, path: &str) -> bool {
+        self.tree.is_ambiguous(path)
     }
-}
 
-impl Index<&str> for Params {
-    type Output = String;
+    /// List pairs of route patterns which could both match a same path,
+    /// like `/posts/:id` and `/posts/new`.
+    pub fn ambiguous_pairs(&self) -> Vec<(String, String)> {
+        let states: Vec<usize> = self.endpoints.keys().copied().collect();
+        let mut pairs = Vec::new();
 
-    fn index(&self, index: &str) -> &Self::Output {
-        match self.map.get(index) {
-            Some(s) => s,
-            None => {
-                panic!("params[{}] did not exist", index)
+        for (i, a) in states.iter().enumerate() {
+            for b in &states[i + 1..] {
+                if self.tree.overlaps(*a, *b) {
+                    pairs.push((self.tree.pattern(*a), self.tree.pattern(*b)));
+                }
             }
         }
+
+        pairs
     }
-}
 
-impl<'a> IntoIterator for &'a Params {
-    type IntoIter = ParamIter<'a>;
-    type Item = (&'a str, &'a str);
+    /// Mark `prefix` as the mount boundary of an app, see [`Router::split_mount`].
+    pub fn set_boundary(&mut self, prefix: impl AsRef<str>) {
+        let prefix = prefix.as_ref().trim_end_matches(self.tree.separator());
+        let state = self.tree.locate(prefix);
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+        self.boundaries.insert(state);
     }
-}
 
-pub struct ParamIter<'a>(btree_map::Iter<'a, String, String>);
+    /// Route the path, then split it into the mount prefix and the in-app leaf
+    /// at the deepest boundary of the matched route.
+    pub fn split_mount<'p>(&self, path: &'p str) -> Option<(&'p str, &'p str)> {
+        let found = self.tree.search(path).ok()?;
+        if !self.endpoints.contains_key(&found.state) {
+            return None;
+        }
 
-impl<'a> Iterator for ParamIter<'a> {
-    type Item = (&'a str, &'a str);
+        // the number of segments of the mount prefix
+        let depth = self
+            .tree
+            .ancestors(found.state)
+            .iter()
+            .rposition(|state| self.boundaries.contains(state))
+            .unwrap_or(0);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.next().map(|(k, v)| (k.as_str(), v.as_str()))
-    }
+        let sep = self.tree.separator();
+        let offset = match depth.checked_sub(1) {
+            Some(n) => {
+                let leading = path.len() - path.trim_start_matches(sep).len();
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.0.size_hint()
+                path[leading..]
+                    .match_indices(sep)
+                    .nth(n)
+                    .map_or(path.len(), |(pos, _)| leading + pos)
+            }
+            None => 0,
+        };
+
+        Some(path.split_at(offset))
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Route the path, also returning the middlewares of the matched route,
+    /// the inherited prefix middlewares come first.
+    pub fn route_with_middleware(&self, path: &str) -> Option<(&T, Params, Vec<MiddlewareId>)> {
+        let found = self.tree.search(path).ok()?;
+        let state = found.state;
+        let (endpoint, params) = self.resolve(found).ok()?;
 
-    #[test]
-    fn basic_router() {
-        let mut router = Router::new();
+        let mut middlewares = Vec::new();
+        for state in self.tree.ancestors(state) {
+            if let Some(mws) = self.prefix_middlewares.get(&state) {
+                middlewares.extend_from_slice(mws);
+            }
+        }
+        if let Some(mws) = self.middlewares.get(&state) {
+            middlewares.extend_from_slice(mws);
+        }
 
-        router.add("/hello", "Hello");
-        router.add("/hell", "Hell");
-        router.add("/world", "World");
+        Some((endpoint, params, middlewares))
+    }
 
-        let (endpoint, params) = router.route("/hello").unwrap();
+    pub fn route(&self, path: &str) -> Option<(&T, Params)> {
+        self.try_route(path).ok()
+    }
 
-        assert_eq!(*endpoint, "Hello");
-        assert_eq!(params, empty_params());
+    /// Route the path, also returning the context of the matched route if any.
+    pub fn route_with_context(&self, path: &str) -> Option<(&T, Option<&C>, Params)> {
+        let found = self.tree.search(path).ok()?;
+        let context = self.contexts.get(&found.state);
+        let (endpoint, params) = self.resolve(found).ok()?;
+
+        Some((endpoint, context, params))
     }
 
-    #[test]
-    fn ambiguous_router() {
-        let mut router = Router::new();
+    pub fn try_route(&self, path: &str) -> Result<(&T, Params), RouteError> {
+        let mut params = Params::new();
+        let endpoint = self.try_route_into(path, &mut params)?;
 
-        router.add("/posts/new", "new");
-        router.add("/posts/:id", "id");
+        Ok((endpoint, params))
+    }
 
-        let (endpoint, params) = router.route("/posts/1").unwrap();
+    /// Route the path into an existing `params`, which is cleared on entry, whether
+    /// the path matches or not. The captured values are still newly allocated.
+    pub fn route_into(&self, path: &str, params: &mut Params) -> Option<&T> {
+        self.try_route_into(path, params).ok()
+    }
 
-        assert_eq!(*endpoint, "id");
-        assert_eq!(params, one_params("id", "1"));
+    fn try_route_into(&self, path: &str, params: &mut Params) -> Result<&T, RouteError> {
+        params.clear();
 
-        let (endpoint, params) = router.route("/posts/new").unwrap();
+        // no endpoint registered, skip searching
+        if self.is_empty() {
+            return Err(RouteError::NotFound);
+        }
 
-        assert_eq!(*endpoint, "new");
-        assert_eq!(params, empty_params());
+        let err = match self.route_path(path, params) {
+            Ok(endpoint) => return Ok(endpoint),
+            Err(err) => err,
+        };
+
+        match self.trailing_slash.other_form(path) {
+            Some(other) => self
+                .trailing_slash
+                .retry(other, err, |p| self.route_path(p, params)),
+            None => Err(err),
+        }
     }
 
-    #[test]
-    fn ambiguous_router_b() {
-        let mut router = Router::new();
+    /// Route the path, `params` is only filled on a match.
+    fn route_path(&self, path: &str, params: &mut Params) -> Result<&T, RouteError> {
+        if let Some((scoped, sep)) = self.scoped_path(path) {
+            if let Ok(endpoint) = self
+                .tree
+                .search(&scoped)
+                .and_then(|found| self.resolve_scoped_into(found, params, Some(sep)))
+            {
+                return Ok(endpoint);
+            }
+        }
 
-        router.add("/posts/:id", "id");
-        router.add("/posts/new", "new");
+        let found = self.tree.search(path)?;
 
-        let (endpoint, params) = router.route("/posts/1").unwrap();
+        self.resolve_into(found, params)
+    }
 
-        assert_eq!(*endpoint, "id");
-        assert_eq!(params, one_params("id", "1"));
+    /// Route a path already split into segments, as `route` does the segments joined by `/`,
+    /// without splitting the path again. A wildcard spanning several segments joins them.
+    ///
+    /// With scopes or a trailing slash policy, the segments are joined and routed as is.
+    pub fn route_segments(&self, segments: &[&str]) -> Option<(&T, Params)> {
+        if self.is_empty() {
+            return None;
+        }
 
-        let (endpoint, params) = router.route("/posts/new").unwrap();
+        if !self.scopes.is_empty() || self.trailing_slash != TrailingSlash::Strict {
+            return self.route(&segments.join(self.tree.separator().encode_utf8(&mut [0; 4])));
+        }
 
-        assert_eq!(*endpoint, "new");
-        assert_eq!(params, empty_params());
+        let found = self.tree.search_segments(segments).ok()?;
+
+        self.resolve(found).ok()
     }
 
-    #[test]
-    fn ambiguous_router_c() {
-        let mut router = Router::new();
+    /// Route the path to the `n`-th matching route in priority order, `0` is the best one.
+    pub fn route_nth(&self, path: &str, n: usize) -> Option<(&T, Params)> {
+        self.tree
+            .search_all(path)
+            .into_iter()
+            .filter_map(|found| self.resolve(found).ok())
+            .nth(n)
+    }
 
-        router.add("/posts/100/comments/10", "100-10");
-        router.add("/posts/:post/comments/100", "post-100");
+    /// Route the path, giving up once more than `max_steps` steps are taken,
+    /// where each candidate route walking one segment counts as a step.
+    ///
+    /// Scopes and the trailing slash policy are not applied.
+    pub fn route_budgeted(
+        &self,
+        path: &str,
+        max_steps: usize,
+    ) -> Result<Option<(&T, Params)>, BudgetExceeded> {
+        let found = self.tree.search_budgeted(path, max_steps)?;
 
-        let (endpoint, params) = router.route("/posts/100/comments/10").unwrap();
+        Ok(found.and_then(|found| self.resolve(found)).ok())
+    }
 
-        assert_eq!(*endpoint, "100-10");
-        assert_eq!(params, empty_params());
+    /// Register routes under the static `prefix`, whose remaining segments are
+    /// delimited by `sep` instead of `/`.
+    ///
+    /// Only [`Router::route`] and [`Router::try_route`] take scopes into account.
+    pub fn scope_separator<F>(&mut self, prefix: impl AsRef<str>, sep: char, f: F)
+    where
+        F: FnOnce(&mut Scope<'_, T, C>),
+    {
+        let prefix = format!("/{}", prefix.as_ref().trim_matches('/'));
 
-        let (endpoint, params) = router.route("/posts/100/comments/100").unwrap();
+        self.add_scope(prefix.clone(), sep);
 
-        assert_eq!(*endpoint, "post-100");
-        assert_eq!(params, one_params("post", "100"));
+        f(&mut Scope {
+            router: self,
+            prefix,
+            sep,
+        });
     }
 
-    #[test]
-    fn multiple_params() {
-        let mut router = Router::new();
+    fn add_scope(&mut self, prefix: String, sep: char) {
+        if !self.scopes.iter().any(|(p, s)| *p == prefix && *s == sep) {
+            self.scopes.push((prefix, sep));
+            // longest prefix first
+            self.scopes.sort_by_key(|(p, _)| std::cmp::Reverse(p.len()));
+        }
+    }
 
-        router.add("/posts/:post_id/comments/:comment_id", "comment");
-        router.add("/posts/:post_id/comments", "comments");
+    /// Rewrite the path inside a scope into `/` delimited segments.
+    fn scoped_path(&self, path: &str) -> Option<(String, char)> {
+        let path = format!("/{}", path.trim_start_matches('/'));
 
-        let (endpoint, params) = router.route("/posts/12/comments/100").unwrap();
-        assert_eq!(*endpoint, "comment");
-        assert_eq!(params, two_params("post_id", "12", "comment_id", "100"));
+        self.scopes.iter().find_map(|(prefix, sep)| {
+            let rest = path.strip_prefix(prefix.as_str())?.strip_prefix('/')?;
+            if rest.contains('/') {
+                return None;
+            }
 
-        let (endpoint, params) = router.route("/posts/12/comments").unwrap();
-        assert_eq!(*endpoint, "comments");
-        assert_eq!(params, one_params("post_id", "12"));
-        assert_eq!(params["post_id"], "12".to_string());
+            Some((format!("{prefix}/{}", rest.replace(*sep, "/")), *sep))
+        })
     }
 
-    #[test]
-    fn wildcard_colon() {
-        let mut router = Router::new();
+    /// Route the path with the params borrowed from it, not copied. Unlike
+    /// [`Router::route`], scopes, the trailing slash policy and param decoding
+    /// are not applied.
+    pub fn route_ref<'a: 'p, 'p>(&'a self, path: &'p str) -> Option<(&'a T, ParamsRef<'p>)> {
+        let found = self.tree.search(path).ok()?;
+        let endpoint = self.endpoints.get(&found.state)?.get();
 
-        router.add("/a/*b", "ab");
-        router.add("/a/:b/c", "abc");
-        router.add("/a/:b/c/:d", "abcd");
+        let params = found
+            .params
+            .into_iter()
+            .filter(|(n, _v)| !n.is_empty())
+            .collect();
 
-        let (endpoint, params) = router.route("/a/foo").unwrap();
-        assert_eq!(*endpoint, "ab");
-        assert_eq!(params, one_params("b", "foo"));
+        Some((endpoint, ParamsRef { params }))
+    }
 
-        let (endpoint, params) = router.route("/a/foo/bar").unwrap();
-        assert_eq!(*endpoint, "ab");
-        assert_eq!(params, one_params("b", "foo/bar"));
+    /// Route the raw request target, the query string is ignored, params are returned
+    /// as byte ranges into `target`, so the dynamic parts can be highlighted.
+    pub fn route_annotate<'a: 'p, 'p>(
+        &'a self,
+        target: &'p str,
+    ) -> Option<(&'a T, ParamSpans<'p>)> {
+        let path = match target.split_once(['?', '#']) {
+            Some((path, _query)) => path,
+            None => target,
+        };
 
-        let (endpoint, params) = router.route("/a/foo/c").unwrap();
-        assert_eq!(*endpoint, "abc");
-        assert_eq!(params, one_params("b", "foo"));
-    }
+        let found = self.tree.search(path).ok()?;
+        let endpoint = self.endpoints.get(&found.state)?.get();
 
-    #[test]
-    fn unnamed_parameters() {
-        let mut router = Router::new();
+        // captured values are slices of `target`
+        let base = target.as_ptr() as usize;
+        let params = found
+            .params
+            .into_iter()
+            .filter(|(n, _v)| !n.is_empty())
+            .map(|(n, v)| {
+                let start = v.as_ptr() as usize - base;
+                (n, start..start + v.len())
+            })
+            .collect();
 
-        router.add("/foo/:/bar", "test");
-        router.add("/bar/*", "bar");
-        router.add("/hello/*world", "hello");
+        Some((endpoint, params))
+    }
 
-        let (endpoint, params) = router.route("/foo/test/bar").unwrap();
-        assert_eq!(*endpoint, "test");
-        assert_eq!(params, empty_params());
+    /// Route the path with static segments only, ignore any param or wildcard route.
+    pub fn route_static_only(&self, path: &str) -> Option<(&T, Params)> {
+        let found = self.tree.search_static(path)?;
 
-        let (endpoint, params) = router.route("/bar/hello").unwrap();
-        assert_eq!(*endpoint, "bar");
-        assert_eq!(params, empty_params());
+        self.resolve(found).ok()
+    }
 
-        let (endpoint, params) = router.route("/hello/world").unwrap();
-        assert_eq!(*endpoint, "hello");
-        assert_eq!(params, one_params("world", "world"));
+    /// Get the endpoint at the exact path following static segments only,
+    /// like `route_static_only` without building params.
+    pub fn route_exact(&self, path: &str) -> Option<&T> {
+        let found = self.tree.search_static(path)?;
+
+        self.endpoints.get(&found.state).map(Endpoint::get)
     }
 
-    #[test]
-    fn modify_router() {
-        let mut router = Router::new();
+    /// Get the segments of the matched template, each flagged whether it is dynamic.
+    pub fn route_template_spans(&self, path: &str) -> Option<Vec<(String, bool)>> {
+        let found = self.tree.search(path).ok()?;
 
-        router.add("/a/b/c", "abc");
-        router.add("/e/:f/g", "efg");
+        self.endpoints
+            .contains_key(&found.state)
+            .then(|| self.tree.segments(found.state))
+    }
 
-        let endpoint = router.route("/a/b/c").unwrap().0;
-        assert_eq!(*endpoint, "abc");
+    /// Route the path and list the relative patterns reachable one segment further,
+    /// e.g. `comments` and `:comment_id` for `/posts/5`.
+    pub fn next_routes(&self, path: &str) -> Option<Vec<String>> {
+        let found = self.tree.search(path).ok()?;
 
-        *router.at_or_default("/a/b/c") = "aabbcc";
+        self.endpoints
+            .contains_key(&found.state)
+            .then(|| self.tree.next_patterns(found.state))
+    }
 
-        let endpoint = router.route("/a/b/c").unwrap().0;
-        assert_eq!(*endpoint, "aabbcc");
+    /// Route the path, along with the pattern of the matched route.
+    pub fn route_match(&self, path: &str) -> Option<RouteMatch<'_, T>> {
+        let found = self.tree.search(path).ok()?;
+        let pattern = self.tree.pattern(found.state);
+        let (endpoint, params) = self.resolve(found).ok()?;
+
+        Some(RouteMatch {
+            endpoint,
+            kind: params.kind,
+            params,
+            pattern,
+        })
+    }
+
+    /// Route the path and return the pattern of the matched route,
+    /// e.g. `/posts/:id` for `/posts/5`.
+    pub fn templatize(&self, path: &str) -> Option<String> {
+        let found = self.tree.search(path).ok()?;
+
+        self.endpoints
+            .contains_key(&found.state)
+            .then(|| self.tree.pattern(found.state))
+    }
+
+    /// Get the interned id of the param name.
+    pub fn param_id(&self, name: &str) -> Option<u32> {
+        self.interner.get(name)
+    }
+
+    /// Route the path, params are keyed by interned ids instead of owned names.
+    pub fn route_interned(&self, path: &str) -> Option<(&T, ParamsInterned<'_>)> {
+        let found = self.tree.search(path).ok()?;
+        let endpoint = self.endpoints.get(&found.state)?.get();
+        let mut params = ParamsInterned::new(&self.interner);
+
+        for (n, v) in found.params {
+            if let Some(id) = self.interner.get(n) {
+                params.values.insert(id, self.param_value(v));
+            }
+        }
+
+        Some((endpoint, params))
+    }
+
+    fn resolve<V: AsRef<str>>(&self, found: nfa::Match<'_, V>) -> Result<(&T, Params), RouteError> {
+        let mut params = Params::new();
+        let endpoint = self.resolve_into(found, &mut params)?;
+
+        Ok((endpoint, params))
+    }
+
+    /// Get the endpoint of the match, `params` is only filled when it has one.
+    fn resolve_into<V: AsRef<str>>(
+        &self,
+        found: nfa::Match<'_, V>,
+        params: &mut Params,
+    ) -> Result<&T, RouteError> {
+        self.resolve_scoped_into(found, params, None)
+    }
+
+    /// Like `resolve_into`, for a match inside a scope delimited by `sep`, whose `/`
+    /// are turned back into `sep` before values are decoded.
+    fn resolve_scoped_into<V: AsRef<str>>(
+        &self,
+        found: nfa::Match<'_, V>,
+        params: &mut Params,
+        sep: Option<char>,
+    ) -> Result<&T, RouteError> {
+        let endpoint = self
+            .endpoints
+            .get(&found.state)
+            .map(Endpoint::get)
+            .ok_or(RouteError::NoEndpoint)?;
+
+        params.kind = found.kind();
+        for (n, v) in found.params {
+            let v = match sep {
+                Some(sep) => {
+                    self.param_value(&v.as_ref().replace('/', sep.encode_utf8(&mut [0; 4])))
+                }
+                None => self.param_value(v.as_ref()),
+            };
+            if n.is_empty() {
+                params.positional.push(v);
+            } else {
+                params.map.insert(n.to_string(), v);
+            }
+        }
+
+        Ok(endpoint)
+    }
+
+    fn param_value(&self, value: &str) -> String {
+        if self.decode_params {
+            percent::decode(value).into_owned()
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Mount the routes of `other` under `path`, a route both routers have takes
+    /// the endpoint of `other`. See [`Router::try_merge`] to refuse that instead.
+    ///
+    /// The path may hold params, as `/tenants/:tenant`, captured by every mounted route.
+    pub fn merge(&mut self, path: impl AsRef<str>, other: Router<T, C>) {
+        self.merge_mapped(path, other);
+    }
+
+    /// Merge like [`Router::merge`], and return the pattern of each route of `other`
+    /// along with its pattern in this router, in the order `other.routes()` lists them.
+    pub fn merge_mapped(
+        &mut self,
+        path: impl AsRef<str>,
+        other: Router<T, C>,
+    ) -> Vec<(String, String)> {
+        let path = path.as_ref().trim_end_matches(self.tree.separator());
+        let state = self.tree.locate(path);
+        let names = self.tree.route_names(path);
+
+        // scopes are keyed by `/` prefixes whatever the separator
+        let base = path.trim_start_matches('/');
+        for (prefix, sep) in &other.scopes {
+            let prefix = match base {
+                "" => prefix.clone(),
+                base => format!("/{base}{prefix}"),
+            };
+            self.add_scope(prefix, *sep);
+        }
+
+        self.merge_at(state, &names, other, |_pattern, _existing, endpoint| {
+            endpoint
+        })
+    }
+
+    /// Merge routes of `other` under `path` like [`Router::merge`], but fail with
+    /// the colliding patterns when both routers have some route, nothing is merged then.
+    pub fn try_merge(
+        &mut self,
+        path: impl AsRef<str>,
+        other: Router<T, C>,
+    ) -> Result<(), Vec<String>> {
+        let path = path.as_ref();
+        let base = path.trim_end_matches(self.tree.separator());
+        let collisions: Vec<String> = other
+            .patterns()
+            .map(|pattern| format!("{base}{pattern}"))
+            .filter(|pattern| self.contains(pattern))
+            .collect();
+
+        if !collisions.is_empty() {
+            return Err(collisions);
+        }
+
+        self.merge(path, other);
+
+        Ok(())
+    }
+
+    /// Chain with a `fallback` router, which is tried when this router fails to route.
+    pub fn with_fallback_router(self, fallback: Router<T, C>) -> ChainedRouter<T, C> {
+        ChainedRouter {
+            primary: self,
+            fallback,
+        }
+    }
+
+    /// Combine routes of `other` into this router, `resolver` is called with the pattern,
+    /// the existing endpoint and the other endpoint, when both routers have the route.
+    pub fn combine_with<F>(&mut self, other: Router<T, C>, resolver: F)
+    where
+        F: Fn(&str, T, T) -> T,
+    {
+        let state = self.tree.start_state();
+
+        for (prefix, sep) in &other.scopes {
+            self.add_scope(prefix.clone(), *sep);
+        }
+
+        self.merge_at(state, &[], other, resolver);
+    }
+
+    /// Merge `other` at the state reached by params named `prefix`, return the old
+    /// and new patterns of its routes.
+    fn merge_at<F>(
+        &mut self,
+        state: usize,
+        prefix: &[String],
+        mut other: Router<T, C>,
+        resolver: F,
+    ) -> Vec<(String, String)>
+    where
+        F: Fn(&str, T, T) -> T,
+    {
+        let right = other.tree.start_state();
+
+        let mut states = self.tree.merge(state, &other.tree, right, prefix);
+        // by the old state, as the endpoints of `other` are ordered
+        states.sort_unstable_by_key(|(_new, old)| *old);
+        let mut mapped = Vec::with_capacity(other.endpoints.len());
+
+        for name in other.interner.ids.keys() {
+            self.interner.intern(name);
+        }
+
+        if let Some(mws) = other.prefix_middlewares.remove(&right) {
+            self.prefix_middlewares
+                .entry(state)
+                .or_default()
+                .extend(mws);
+        }
+
+        for (new, old) in states {
+            if let Some(ep) = other.endpoints.remove(&old) {
+                mapped.push((other.tree.pattern(old), self.tree.pattern(new)));
+                let ep = match self.endpoints.remove(&new) {
+                    Some(existing) => {
+                        let pattern = self.tree.pattern(new);
+                        Endpoint::ready(resolver(&pattern, existing.into_inner(), ep.into_inner()))
+                    }
+                    None => ep,
+                };
+                self.endpoints.insert(new, ep);
+            }
+            if let Some(ctx) = other.contexts.remove(&old) {
+                self.contexts.insert(new, ctx);
+            }
+            if let Some(mws) = other.middlewares.remove(&old) {
+                self.middlewares.insert(new, mws);
+            }
+            if let Some(mws) = other.prefix_middlewares.remove(&old) {
+                self.prefix_middlewares.entry(new).or_default().extend(mws);
+            }
+            if other.boundaries.contains(&old) {
+                self.boundaries.insert(new);
+            }
+        }
+
+        mapped
+    }
+}
+
+/// Routes under a prefix, whose segments are delimited by a custom separator.
+pub struct Scope<'r, T, C = ()> {
+    router: &'r mut Router<T, C>,
+    prefix: String,
+    sep: char,
+}
+
+impl<T, C> Scope<'_, T, C> {
+    pub fn add(&mut self, pattern: impl AsRef<str>, endpoint: T) {
+        let pattern = pattern
+            .as_ref()
+            .trim_start_matches(self.sep)
+            .replace(self.sep, "/");
+
+        self.router
+            .add(format!("{}/{}", self.prefix, pattern), endpoint);
+    }
+}
+
+/// Endpoint slot of a pattern, see [`Router::entry`].
+pub struct Entry<'r, T, C = ()> {
+    router: &'r mut Router<T, C>,
+    pattern: String,
+}
+
+impl<'r, T, C> Entry<'r, T, C> {
+    /// The pattern of the entry.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Insert `endpoint` when the pattern has none, return the endpoint in place.
+    pub fn or_insert(self, endpoint: T) -> &'r mut T {
+        self.or_insert_with(|| endpoint)
+    }
+
+    /// Insert the endpoint built by `f` when the pattern has none, return the endpoint in place.
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'r mut T {
+        let state = self.router.insert(&self.pattern);
+
+        self.router
+            .endpoints
+            .entry(state)
+            .or_insert_with(|| Endpoint::ready(f()))
+            .get_mut()
+    }
+
+    /// Modify the endpoint in place when the pattern has one.
+    pub fn and_modify(self, f: impl FnOnce(&mut T)) -> Self {
+        if let Some(endpoint) = self.router.get_mut(&self.pattern) {
+            f(endpoint);
+        }
+
+        self
+    }
+}
+
+impl<'r, T: Default, C> Entry<'r, T, C> {
+    /// Insert the default endpoint when the pattern has none, return the endpoint in place.
+    pub fn or_default(self) -> &'r mut T {
+        self.or_insert_with(T::default)
+    }
+}
+
+impl<T, C> Router<T, C> {
+    /// Freeze the router behind an `Arc` for routing only, cloning it shares the routes
+    /// instead of copying endpoints, and it is `Send + Sync` when `T` and `C` are.
+    pub fn into_shared(self) -> SharedRouter<T, C> {
+        SharedRouter {
+            router: Arc::new(self),
+        }
+    }
+
+    /// Get the endpoint slot of the pattern, to read or insert it in place.
+    /// The pattern is only added once an endpoint is inserted.
+    pub fn entry(&mut self, pattern: impl AsRef<str>) -> Entry<'_, T, C> {
+        Entry {
+            router: self,
+            pattern: pattern.as_ref().to_string(),
+        }
+    }
+}
+
+impl<T: Copy, C> Router<T, C> {
+    /// Route the path, returning a copy of the endpoint.
+    pub fn route_copied(&self, path: &str) -> Option<(T, Params)> {
+        self.route(path)
+            .map(|(endpoint, params)| (*endpoint, params))
+    }
+}
+
+impl<T: Clone, C> Router<T, C> {
+    /// Route the path, returning a clone of the endpoint.
+    pub fn route_cloned(&self, path: &str) -> Option<(T, Params)> {
+        self.route(path)
+            .map(|(endpoint, params)| (endpoint.clone(), params))
+    }
+}
+
+impl<T: Default, C> Router<T, C> {
+    pub fn at_or_default(&mut self, path: impl AsRef<str>) -> &mut T {
+        self.entry(path).or_default()
+    }
+}
+
+impl<T: Default, C> Default for Router<T, C> {
+    fn default() -> Self {
+        Router::with_context()
+    }
+}
+
+/// Build a router from `(pattern, endpoint)` pairs, a later duplicate pattern overwrites
+/// the earlier one like [`Router::add`].
+impl<P: AsRef<str>, T, C> FromIterator<(P, T)> for Router<T, C> {
+    fn from_iter<I: IntoIterator<Item = (P, T)>>(iter: I) -> Self {
+        let mut router = Router::with_context();
+        router.extend(iter);
+        router
+    }
+}
+
+impl<P: AsRef<str>, T, C> Extend<(P, T)> for Router<T, C> {
+    fn extend<I: IntoIterator<Item = (P, T)>>(&mut self, iter: I) {
+        for (pattern, endpoint) in iter {
+            self.add(pattern, endpoint);
+        }
+    }
+}
+
+/// Router trying a primary router first, then a fallback one.
+#[derive(Debug, Clone)]
+pub struct ChainedRouter<T, C = ()> {
+    primary: Router<T, C>,
+    fallback: Router<T, C>,
+}
+
+impl<T, C> ChainedRouter<T, C> {
+    pub fn route(&self, path: &str) -> Option<(&T, Params)> {
+        self.try_route(path).ok()
+    }
+
+    /// Route by the primary router, then by the fallback one when the primary finds
+    /// no route, the error of the fallback is returned when neither matches. Any
+    /// other error of the primary, as a trailing slash redirect, is returned as is.
+    pub fn try_route(&self, path: &str) -> Result<(&T, Params), RouteError> {
+        match self.primary.try_route(path) {
+            Err(RouteError::NotFound) => self.fallback.try_route(path),
+            found => found,
+        }
+    }
+
+    /// Split back into the primary and the fallback router.
+    pub fn into_parts(self) -> (Router<T, C>, Router<T, C>) {
+        (self.primary, self.fallback)
+    }
+}
+
+/// Finalized [`Router`] behind an `Arc`, clones share the states and endpoints,
+/// see [`Router::into_shared`].
+#[derive(Debug)]
+pub struct SharedRouter<T, C = ()> {
+    router: Arc<Router<T, C>>,
+}
+
+impl<T, C> SharedRouter<T, C> {
+    pub fn route(&self, path: &str) -> Option<(&T, Params)> {
+        self.router.route(path)
+    }
+
+    /// Borrow the params from the path, see [`Router::route_ref`].
+    pub fn route_ref<'a: 'p, 'p>(&'a self, path: &'p str) -> Option<(&'a T, ParamsRef<'p>)> {
+        self.router.route_ref(path)
+    }
+}
+
+impl<T, C> Clone for SharedRouter<T, C> {
+    fn clone(&self) -> Self {
+        SharedRouter {
+            router: Arc::clone(&self.router),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeRouter<T> {
+    tree: crate::tree::Tree<T>,
+    decode_params: bool,
+    trailing_slash: TrailingSlash,
+    fallback: Option<T>,
+}
+
+impl<T> TreeRouter<T> {
+    pub fn new() -> Self {
+        TreeRouter {
+            tree: crate::tree::Tree::new(),
+            decode_params: false,
+            trailing_slash: TrailingSlash::Strict,
+            fallback: None,
+        }
+    }
+
+    /// Create a router whose segments are separated by `sep` instead of `/`,
+    /// see [`Router::with_separator`].
+    pub fn with_separator(sep: char) -> Self {
+        check_separator(sep);
+
+        let mut router = TreeRouter::new();
+        router.tree.set_separator(sep);
+        router
+    }
+
+    /// Create a router with room for `nodes` tree nodes, see [`Router::with_capacity`].
+    pub fn with_capacity(nodes: usize) -> Self {
+        let mut router = TreeRouter::new();
+        router.tree.reserve(nodes);
+        router
+    }
+
+    /// Percent-decode captured param and wildcard values, invalid sequences are left as is.
+    /// Params borrowed by `route_ref` are never decoded.
+    pub fn set_decode_params(&mut self, enable: bool) {
+        self.decode_params = enable;
+    }
+
+    /// Percent-decode path segments before matching static segments,
+    /// so `/a%20b` matches a route registered as `/a b`. Params are captured as is.
+    pub fn set_decode_static(&mut self, enable: bool) {
+        self.tree.set_decode_static(enable);
+    }
+
+    /// Match static segments ASCII case-insensitively, captured values keep their case.
+    ///
+    /// Enable it before adding routes, static segments are then stored lowercased,
+    /// so patterns only differing in case share a route. Turning it on afterwards is
+    /// not supported, such routes are only found by a slower scan, and a mixed case
+    /// path may stop at a sibling differing in case.
+    pub fn case_insensitive(&mut self, enable: bool) {
+        self.tree.set_case_insensitive(enable);
+    }
+
+    /// Reserve segments which params never capture, see [`Router::set_reserved_segments`].
+    pub fn set_reserved_segments(&mut self, words: &[&str]) {
+        self.tree.set_reserved(words);
+    }
+
+    /// Set how a trailing slash in the routed path is treated.
+    pub fn trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
+
+    /// Add the route, overwriting any conflicting one. The pattern is not
+    /// validated, see [`TreeRouter::try_add`].
+    pub fn add(&mut self, pattern: impl AsRef<str>, endpoint: T) {
+        let pattern = pattern.as_ref();
+        self.tree.insert(pattern, endpoint);
+    }
+
+    /// Add the route, fail when the pattern is already registered, when a param
+    /// or wildcard at the same position is named differently, or when the pattern
+    /// or a param constraint is invalid.
+    ///
+    /// Unlike [`Router`], a wildcard may be followed by more segments, it then spans
+    /// as many segments as possible while the rest still matches them.
+    pub fn try_add(&mut self, pattern: impl AsRef<str>, endpoint: T) -> Result<(), AddError> {
+        let pattern = pattern.as_ref();
+        let sep = self.tree.separator();
+        validate_segments(pattern, sep, true).map_err(AddError::InvalidPattern)?;
+
+        for seg in pattern.split(sep) {
+            if let Some(param) = seg.strip_prefix(':') {
+                let param = param.strip_suffix('?').unwrap_or(param);
+                constraint::Constraint::try_parse(param).map_err(AddError::InvalidConstraint)?;
+            }
+        }
+
+        if let Some(node) = self.tree.find_conflict(pattern) {
+            return Err(AddError::Conflict(RouteConflict {
+                existing: self.tree.pattern(node),
+            }));
+        }
+
+        self.add(pattern, endpoint);
+
+        Ok(())
+    }
+
+    /// Remove the route of the pattern, return the removed endpoint.
+    pub fn remove(&mut self, pattern: impl AsRef<str>) -> Option<T> {
+        self.tree.remove(pattern.as_ref())
+    }
+
+    /// Set the endpoint of `route_or_fallback` when no route matches, any path falls
+    /// back to it, even `/`. Return the previous fallback.
+    pub fn set_fallback(&mut self, endpoint: T) -> Option<T> {
+        self.fallback.replace(endpoint)
+    }
+
+    /// Whether an endpoint is registered for the exact pattern, unlike `route`
+    /// the pattern is not matched as a concrete path.
+    pub fn contains(&self, pattern: impl AsRef<str>) -> bool {
+        self.tree.contains(pattern.as_ref())
+    }
+
+    /// Number of registered routes, counted over the tree nodes.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all routes, keeping the allocated capacity where possible.
+    /// The fallback is not a route and is kept.
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    /// Iterate all registered routes, with their reconstructed patterns.
+    pub fn routes(&self) -> impl Iterator<Item = (String, &T)> {
+        self.tree.routes()
+    }
+
+    /// What may follow the prefix, static segments and the `:` and `*` markers of
+    /// params and wildcards, for completion over the route table.
+    pub fn children_of(&self, prefix: impl AsRef<str>) -> Vec<String> {
+        self.tree.children_of(prefix.as_ref())
+    }
+
+    /// Size of the tree, each node counts as a state.
+    pub fn stats(&self) -> RouterStats {
+        self.tree.stats()
+    }
+
+    /// Graphviz DOT of the tree, for debugging why a path does not match.
+    pub fn to_dot(&self) -> String {
+        self.tree.to_dot()
+    }
+
+    /// Route the path with the params borrowed from it, not copied. The trailing
+    /// slash policy and param decoding of [`TreeRouter::route`] are skipped.
+    pub fn route_ref<'a: 'p, 'p>(&'a self, path: &'p str) -> Option<(&'a T, ParamsRef<'p>)> {
+        self.tree
+            .search_ref(path)
+            .ok()
+            .map(|(endpoint, params)| (endpoint, ParamsRef { params }))
+    }
+
+    /// Mount the routes of `other` under `path`, a route both routers have takes
+    /// the endpoint of `other`. See [`TreeRouter::try_merge`] to refuse that instead.
+    ///
+    /// The path may hold params, as `/tenants/:tenant`, captured by every mounted route.
+    pub fn merge(&mut self, path: impl AsRef<str>, other: TreeRouter<T>) {
+        self.tree.merge(path.as_ref(), other.tree);
+    }
+
+    /// Merge like [`TreeRouter::merge`], and return the pattern of each route of `other`
+    /// along with its pattern in this router, in the order `other.routes()` lists them.
+    pub fn merge_mapped(
+        &mut self,
+        path: impl AsRef<str>,
+        other: TreeRouter<T>,
+    ) -> Vec<(String, String)> {
+        self.tree.merge(path.as_ref(), other.tree)
+    }
+
+    /// Merge routes of `other` under `path` like [`TreeRouter::merge`], but fail with
+    /// the colliding patterns when both routers have some route, nothing is merged then.
+    pub fn try_merge(
+        &mut self,
+        path: impl AsRef<str>,
+        other: TreeRouter<T>,
+    ) -> Result<(), Vec<String>> {
+        let path = path.as_ref();
+        let base = path.trim_end_matches(self.tree.separator());
+        let collisions: Vec<String> = other
+            .routes()
+            .map(|(pattern, _)| format!("{base}{pattern}"))
+            .filter(|pattern| self.contains(pattern))
+            .collect();
+
+        if !collisions.is_empty() {
+            return Err(collisions);
+        }
+
+        self.merge(path, other);
+
+        Ok(())
+    }
+
+    pub fn route(&self, path: &str) -> Option<(&T, Params)> {
+        self.try_route(path).ok()
+    }
+
+    /// Route the path, or give the fallback with empty params when no route matches.
+    /// `None` only when no fallback is set either.
+    pub fn route_or_fallback(&self, path: &str) -> Option<(&T, Params)> {
+        self.route(path).or_else(|| {
+            self.fallback
+                .as_ref()
+                .map(|endpoint| (endpoint, Params::new()))
+        })
+    }
+
+    pub fn try_route(&self, path: &str) -> Result<(&T, Params), RouteError> {
+        let err = match self.tree.search(path) {
+            Ok((endpoint, p, kind)) => return Ok((endpoint, self.params(p, kind))),
+            Err(err) => err,
+        };
+
+        match self.trailing_slash.other_form(path) {
+            Some(other) => self.trailing_slash.retry(other, err, |p| {
+                let (endpoint, p, kind) = self.tree.search(p)?;
+                Ok((endpoint, self.params(p, kind)))
+            }),
+            None => Err(err),
+        }
+    }
+
+    /// Route the path, along with the pattern of the matched route.
+    pub fn route_match(&self, path: &str) -> Option<RouteMatch<'_, T>> {
+        let (endpoint, p, kind, pattern) = self.tree.search_pattern(path).ok()?;
+
+        Some(RouteMatch {
+            endpoint,
+            params: self.params(p, kind),
+            kind,
+            pattern,
+        })
+    }
+
+    fn params(&self, p: tree::ParamMap, kind: MatchKind) -> Params {
+        tree_params(p, kind, self.decode_params)
+    }
+
+    /// Flatten into an immutable router for lookups only, routing the same way.
+    pub fn freeze(self) -> FrozenRouter<T> {
+        FrozenRouter {
+            tree: self.tree.freeze(),
+            decode_params: self.decode_params,
+            trailing_slash: self.trailing_slash,
+            fallback: self.fallback,
+        }
+    }
+
+    /// Route the path, telling a known prefix of some routes apart from an unknown path.
+    pub fn route_detailed(&self, path: &str) -> RouteLookup<'_, T> {
+        match self.try_route(path) {
+            Ok((endpoint, params)) => RouteLookup::Matched(endpoint, params),
+            Err(RouteError::NoEndpoint) => RouteLookup::PartialPrefix,
+            Err(_) => RouteLookup::NotFound,
+        }
+    }
+}
+
+fn tree_params(p: tree::ParamMap, kind: MatchKind, decode_params: bool) -> Params {
+    let mut params = Params::new();
+    params.kind = kind;
+
+    // keyed by node, so unnamed params come in path order
+    for (_k, (n, v)) in p {
+        let v = if decode_params {
+            percent::decode(&v).into_owned()
+        } else {
+            v
+        };
+        if n.is_empty() {
+            params.positional.push(v);
+        } else {
+            params.map.insert(n, v);
+        }
+    }
+
+    params
+}
+
+impl<T: Default> TreeRouter<T> {
+    pub fn at_or_default(&mut self, pattern: impl AsRef<str>) -> &mut T {
+        let endpoint = self.tree.at_route(pattern.as_ref());
+
+        let data = &mut endpoint.data;
+
+        match data {
+            Some(ep) => ep,
+            None => {
+                *data = Some(T::default());
+                data.as_mut().unwrap()
+            }
+        }
+    }
+}
+
+impl<T: Default> Default for TreeRouter<T> {
+    fn default() -> Self {
+        TreeRouter::new()
+    }
+}
+
+/// Build a router from `(pattern, endpoint)` pairs, a later duplicate pattern overwrites
+/// the earlier one like [`TreeRouter::add`].
+impl<P: AsRef<str>, T> FromIterator<(P, T)> for TreeRouter<T> {
+    fn from_iter<I: IntoIterator<Item = (P, T)>>(iter: I) -> Self {
+        let mut router = TreeRouter::new();
+        router.extend(iter);
+        router
+    }
+}
+
+impl<P: AsRef<str>, T> Extend<(P, T)> for TreeRouter<T> {
+    fn extend<I: IntoIterator<Item = (P, T)>>(&mut self, iter: I) {
+        for (pattern, endpoint) in iter {
+            self.add(pattern, endpoint);
+        }
+    }
+}
+
+/// [`TreeRouter`] flattened into contiguous arrays once all routes are added,
+/// see [`TreeRouter::freeze`].
+#[derive(Debug, Clone)]
+pub struct FrozenRouter<T> {
+    tree: tree::Frozen<T>,
+    decode_params: bool,
+    trailing_slash: TrailingSlash,
+    fallback: Option<T>,
+}
+
+impl<T> FrozenRouter<T> {
+    pub fn route(&self, path: &str) -> Option<(&T, Params)> {
+        let err = match self.tree.search(path) {
+            Ok((endpoint, p, kind)) => {
+                return Some((endpoint, tree_params(p, kind, self.decode_params)))
+            }
+            Err(err) => err,
+        };
+
+        let other = self.trailing_slash.other_form(path)?;
+        self.trailing_slash
+            .retry(other, err, |p| {
+                let (endpoint, p, kind) = self.tree.search(p)?;
+                Ok((endpoint, tree_params(p, kind, self.decode_params)))
+            })
+            .ok()
+    }
+
+    /// See [`TreeRouter::route_or_fallback`].
+    pub fn route_or_fallback(&self, path: &str) -> Option<(&T, Params)> {
+        self.route(path).or_else(|| {
+            self.fallback
+                .as_ref()
+                .map(|endpoint| (endpoint, Params::new()))
+        })
+    }
+}
+
+/// Size of a compiled router, see [`Router::stats`] and [`TreeRouter::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RouterStats {
+    /// States, or tree nodes, including the root.
+    pub state_count: usize,
+    /// States ending a route.
+    pub accepting_count: usize,
+    /// Most segments from the root to any state.
+    pub max_depth: usize,
+    /// Param edges, constrained or not, wildcards excluded.
+    pub param_edge_count: usize,
+}
+
+/// Reason why a path could not be routed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// No registered pattern matches the path.
+    NotFound,
+    /// The path matches a known prefix, but no endpoint is attached to it.
+    NoEndpoint,
+    /// The path only matches with its trailing slash toggled, redirect to the canonical path.
+    Redirect(String),
+    /// The path matches, but no endpoint is registered for the method,
+    /// along with the methods allowed.
+    MethodNotAllowed(Vec<String>),
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::NotFound => write!(f, "route not found"),
+            RouteError::NoEndpoint => write!(f, "route has no endpoint"),
+            RouteError::Redirect(path) => write!(f, "route redirect to `{path}`"),
+            RouteError::MethodNotAllowed(allowed) => {
+                write!(
+                    f,
+                    "route method not allowed, allow `{}`",
+                    allowed.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// Matched route, along with its pattern, e.g. `/posts/:id` for `/posts/42`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteMatch<'a, T> {
+    pub endpoint: &'a T,
+    pub params: Params,
+    pub pattern: String,
+    pub kind: MatchKind,
+}
+
+impl<'a, T> RouteMatch<'a, T> {
+    /// Split into the endpoint, params and pattern.
+    pub fn into_parts(self) -> (&'a T, Params, String) {
+        (self.endpoint, self.params, self.pattern)
+    }
+
+    /// Whether a wildcard of the route took part of the path.
+    pub fn matched_wildcard(&self) -> bool {
+        self.kind == MatchKind::Wildcard
+    }
+}
+
+/// Most dynamic segment of a matched route, ordered from static to wildcard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchKind {
+    /// Nothing was captured, the path is matched exactly.
+    #[default]
+    Static,
+    /// Some param captured a segment, but no wildcard took part.
+    Param,
+    /// A wildcard took part of the path, maybe an empty tail.
+    Wildcard,
+}
+
+impl MatchKind {
+    /// Kind of a search result, by whether a wildcard took the tail of the path
+    /// and whether anything was captured.
+    pub(crate) fn found(wildcard: bool, captured: bool) -> Self {
+        match (wildcard, captured) {
+            (true, _) => MatchKind::Wildcard,
+            (false, true) => MatchKind::Param,
+            (false, false) => MatchKind::Static,
+        }
+    }
+}
+
+/// Outcome of routing a path, see [`TreeRouter::route_detailed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteLookup<'a, T> {
+    /// The path matches a route.
+    Matched(&'a T, Params),
+    /// The path leads to a prefix of some routes, but is not a route itself.
+    PartialPrefix,
+    /// The path is unknown.
+    NotFound,
+}
+
+/// How a trailing slash in the routed path is treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingSlash {
+    /// `/posts/` and `/posts` are different paths.
+    #[default]
+    Strict,
+    /// `/posts/` and `/posts` match the same route.
+    Ignore,
+    /// When only the other form matches, fail with [`RouteError::Redirect`].
+    RedirectTo,
+}
+
+impl TrailingSlash {
+    /// The path with its trailing slash toggled, when the policy looks at it.
+    /// The root path has no other form.
+    fn other_form(self, path: &str) -> Option<String> {
+        if self == TrailingSlash::Strict || path.trim_matches('/').is_empty() {
+            return None;
+        }
+
+        match path.strip_suffix('/') {
+            Some(p) => Some(p.to_string()),
+            None => Some(format!("{path}/")),
+        }
+    }
+
+    /// Route the other form of the path, keep the original error when it does not match either.
+    fn retry<R>(
+        self,
+        other: String,
+        err: RouteError,
+        route: impl FnOnce(&str) -> Result<R, RouteError>,
+    ) -> Result<R, RouteError> {
+        match route(&other) {
+            Ok(found) => match self {
+                TrailingSlash::RedirectTo => Err(RouteError::Redirect(other)),
+                _ => Ok(found),
+            },
+            Err(_) => Err(err),
+        }
+    }
+}
+
+/// The pattern conflicts with an existing route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteConflict {
+    existing: String,
+}
+
+impl RouteConflict {
+    /// The existing pattern which conflicts with the new one.
+    pub fn existing(&self) -> &str {
+        &self.existing
+    }
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "route conflicts with `{}`", self.existing)
+    }
+}
+
+impl std::error::Error for RouteConflict {}
+
+/// Reason why a route could not be added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddError {
+    /// The pattern conflicts with an existing route.
+    Conflict(RouteConflict),
+    /// A param constraint is unknown, or not a valid regex.
+    InvalidConstraint(String),
+    /// The pattern is malformed, see [`validate_pattern`].
+    InvalidPattern(PatternError),
+}
+
+impl AddError {
+    /// The conflict, when the pattern conflicts with an existing route.
+    pub fn conflict(&self) -> Option<&RouteConflict> {
+        match self {
+            AddError::Conflict(conflict) => Some(conflict),
+            AddError::InvalidConstraint(_) | AddError::InvalidPattern(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for AddError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddError::Conflict(conflict) => conflict.fmt(f),
+            AddError::InvalidConstraint(reason) => f.write_str(reason),
+            AddError::InvalidPattern(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AddError {}
+
+/// Malformed pattern, segments are indexed from `0` after the leading `/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    /// A wildcard is followed by more segments, as `/*path/more`.
+    WildcardNotLast { index: usize, segment: String },
+    /// An interior segment is empty, as from `/a//b`.
+    EmptySegment { index: usize },
+    /// A param or wildcard name contains a reserved char, one of `:*?<>()\`.
+    InvalidName { index: usize, segment: String },
+    /// A param or wildcard name is used by an earlier segment, as `/a/:id/b/:id`.
+    DuplicateName { index: usize, segment: String },
+}
+
+impl PatternError {
+    /// Index of the offending segment.
+    pub fn index(&self) -> usize {
+        match self {
+            PatternError::WildcardNotLast { index, .. }
+            | PatternError::EmptySegment { index }
+            | PatternError::InvalidName { index, .. }
+            | PatternError::DuplicateName { index, .. } => *index,
+        }
+    }
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::WildcardNotLast { index, segment } => {
+                write!(
+                    f,
+                    "wildcard `{segment}` of segment {index} is not the last segment"
+                )
+            }
+            PatternError::EmptySegment { index } => write!(f, "segment {index} is empty"),
+            PatternError::InvalidName { index, segment } => {
+                write!(f, "name of segment {index} `{segment}` has a reserved char")
+            }
+            PatternError::DuplicateName { index, segment } => {
+                write!(f, "name of segment {index} `{segment}` is already used")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Check the pattern is well formed: wildcards are only the last segment, param and
+/// wildcard names have no reserved chars and are unique, and no interior segment is empty.
+/// Unnamed params and wildcards, as `:` and `*`, may repeat. Optional params, as `:b?`,
+/// are only for [`TreeRouter`] and rejected here.
+///
+/// Param constraints are not checked here, see [`Router::try_add`].
+pub fn validate_pattern(pattern: impl AsRef<str>) -> Result<(), PatternError> {
+    validate_segments(pattern.as_ref(), '/', false)
+}
+
+/// Chars which never separate segments, as they mark params, wildcards and escapes.
+const RESERVED: [char; 8] = [':', '*', '?', '<', '>', '(', ')', '\\'];
+
+fn check_separator(sep: char) {
+    assert!(
+        !RESERVED.contains(&sep),
+        "separator `{sep}` is reserved for patterns"
+    );
+}
+
+/// [`validate_pattern`] with segments separated by `sep`, with `tree` the syntax only
+/// [`TreeRouter`] knows is allowed: a single wildcard may be followed by other segments,
+/// and a param may be optional.
+fn validate_segments(pattern: &str, sep: char, tree: bool) -> Result<(), PatternError> {
+    let segs: Vec<&str> = pattern.trim_start_matches(sep).split(sep).collect();
+    let last = segs.len() - 1;
+    let mut names = BTreeSet::new();
+    let wildcards: Vec<usize> = (0..segs.len())
+        .filter(|index| segs[*index].starts_with('*'))
+        .collect();
+    if let Some(&index) = wildcards.first() {
+        if index != last && (!tree || wildcards.len() > 1) {
+            let segment = segs[index].to_string();
+            return Err(PatternError::WildcardNotLast { index, segment });
+        }
+    }
+
+    for (index, seg) in segs.iter().enumerate() {
+        let segment = seg.to_string();
+        let name = match seg.chars().next() {
+            Some(':') => {
+                let param = &seg[1..];
+                let param = match param.strip_suffix('?') {
+                    Some(param) if tree => param,
+                    Some(_) => return Err(PatternError::InvalidName { index, segment }),
+                    None => param,
+                };
+                constraint::Constraint::name(param)
+            }
+            Some('*') => &seg[1..],
+            Some(_) => continue,
+            None if index != last => return Err(PatternError::EmptySegment { index }),
+            None => continue,
+        };
+
+        if name.contains(RESERVED) {
+            return Err(PatternError::InvalidName { index, segment });
+        }
+        if !name.is_empty() && !names.insert(name) {
+            return Err(PatternError::DuplicateName { index, segment });
+        }
+    }
+
+    Ok(())
+}
+
+/// Routing took more steps than the given budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetExceeded;
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "route budget exceeded")
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Params captured by routing, with the `serde` feature they serialize as a map
+/// of names to values.
+#[derive(Debug, Clone, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Params {
+    map: BTreeMap<String, String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    positional: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    kind: MatchKind,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Params {
+            map: BTreeMap::new(),
+            positional: Vec::new(),
+            kind: MatchKind::Static,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.positional.clear();
+        self.kind = MatchKind::Static;
+    }
+
+    /// How the route matched the path, `Static` for params built by hand.
+    pub fn kind(&self) -> MatchKind {
+        self.kind
+    }
+
+    /// Whether a wildcard of the route took part of the path.
+    pub fn matched_wildcard(&self) -> bool {
+        self.kind == MatchKind::Wildcard
+    }
+
+    /// Values of unnamed params like `:` and `*` in path order, they have no key
+    /// and are left out of `find` and `iter`.
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    pub fn find(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.map.get(key.as_ref()).map(|s| s.as_str())
+    }
+
+    /// Alias of [`Params::find`], the non-panicking counterpart of `params[key]`.
+    pub fn get(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.find(key)
+    }
+
+    /// Find the param, panics with `msg` when absent.
+    #[track_caller]
+    pub fn expect(&self, key: impl AsRef<str>, msg: &str) -> &str {
+        let key = key.as_ref();
+
+        match self.find(key) {
+            Some(v) => v,
+            None => panic!("{msg}: params[{key}] did not exist"),
+        }
+    }
+
+    pub fn contains_key(&self, key: impl AsRef<str>) -> bool {
+        self.map.contains_key(key.as_ref())
+    }
+
+    /// Find the param and parse it into `T`.
+    ///
+    /// Returns `None` when the param is absent, `Some(Err(..))` when it fails to parse.
+    pub fn find_as<T: FromStr>(&self, key: impl AsRef<str>) -> Option<Result<T, T::Err>> {
+        self.find(key).map(|v| v.parse())
+    }
+
+    /// Find the param, fallback to `default` when absent.
+    pub fn find_or<'a>(&'a self, key: impl AsRef<str>, default: &'a str) -> &'a str {
+        self.find(key).unwrap_or(default)
+    }
+
+    /// Find the param and parse it into `T`, fallback to `default` when absent or unparseable.
+    pub fn get_or<T: FromStr>(&self, key: impl AsRef<str>, default: T) -> T {
+        match self.find_as(key) {
+            Some(Ok(v)) => v,
+            _ => default,
+        }
+    }
+
+    /// Find the param and split it into path segments, meant for wildcard tails.
+    /// An empty tail yields no segments.
+    pub fn find_segments(&self, key: impl AsRef<str>) -> Option<Vec<&str>> {
+        self.find_segments_by(key, '/')
+    }
+
+    /// Like [`Params::find_segments`], for a router with a custom separator.
+    pub fn find_segments_by(&self, key: impl AsRef<str>, sep: char) -> Option<Vec<&str>> {
+        self.find(key).map(|value| match value {
+            "" => Vec::new(),
+            value => value.split(sep).collect(),
+        })
+    }
+
+    pub fn insert(&mut self, key: impl ToString, value: impl ToString) -> Option<String> {
+        self.map.insert(key.to_string(), value.to_string())
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<str>) -> Option<String> {
+        self.map.remove(key.as_ref())
+    }
+
+    pub fn iter(&self) -> ParamIter<'_> {
+        ParamIter(self.map.iter())
+    }
+}
+
+/// Params are equal when they hold the same values, however they matched.
+impl PartialEq for Params {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map && self.positional == other.positional
+    }
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params::new()
+    }
+}
+
+/// Panics when the param is absent, use [`Params::get`] or [`Params::find_or`] instead
+/// where the param may be missing, or [`Params::expect`] to panic with context.
+impl Index<&str> for Params {
+    type Output = String;
+
+    fn index(&self, index: &str) -> &Self::Output {
+        match self.map.get(index) {
+            Some(s) => s,
+            None => {
+                panic!("params[{}] did not exist", index)
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Params {
+    type IntoIter = ParamIter<'a>;
+    type Item = (&'a str, &'a str);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct ParamIter<'a>(btree_map::Iter<'a, String, String>);
+
+impl<'a> Iterator for ParamIter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Param names along with the byte ranges of their values in the routed target.
+pub type ParamSpans<'a> = Vec<(&'a str, Range<usize>)>;
+
+/// Params borrowed from the routed path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParamsRef<'a> {
+    params: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> ParamsRef<'a> {
+    pub fn find(&self, key: impl AsRef<str>) -> Option<&'a str> {
+        let key = key.as_ref();
+
+        self.params
+            .iter()
+            .find(|(n, _v)| *n == key)
+            .map(|(_n, v)| *v)
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.params.iter().copied()
+    }
+
+    /// Copy into owned `Params`.
+    pub fn to_params(&self) -> Params {
+        let mut params = Params::new();
+
+        for (n, v) in self.iter() {
+            params.insert(n, v);
+        }
+
+        params
+    }
+}
+
+/// Assigns each distinct param name a compact id.
+#[derive(Debug, Clone)]
+struct Interner {
+    ids: BTreeMap<String, u32>,
+}
+
+impl Interner {
+    fn new() -> Self {
+        Interner {
+            ids: BTreeMap::new(),
+        }
+    }
+
+    fn intern(&mut self, name: &str) -> u32 {
+        let next = self.ids.len() as u32;
+
+        *self.ids.entry(name.to_string()).or_insert(next)
+    }
+
+    fn intern_pattern(&mut self, pattern: &str, sep: char) {
+        for seg in pattern.split(sep) {
+            if let Some(name) = seg.strip_prefix([':', '*']) {
+                let name = constraint::Constraint::name(name);
+                if !name.is_empty() {
+                    self.intern(name);
+                }
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+}
+
+/// Params keyed by interned ids, names are resolved through the router.
+#[derive(Debug, Clone)]
+pub struct ParamsInterned<'a> {
+    interner: &'a Interner,
+    values: BTreeMap<u32, String>,
+}
+
+impl<'a> ParamsInterned<'a> {
+    fn new(interner: &'a Interner) -> Self {
+        ParamsInterned {
+            interner,
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn find(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.interner
+            .get(key.as_ref())
+            .and_then(|id| self.find_id(id))
+    }
+
+    pub fn find_id(&self, id: u32) -> Option<&str> {
+        self.values.get(&id).map(|s| s.as_str())
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.values.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn basic_router() {
+        let mut router = Router::new();
+
+        router.add("/hello", "Hello");
+        router.add("/hell", "Hell");
+        router.add("/world", "World");
+
+        let (endpoint, params) = router.route("/hello").unwrap();
+
+        assert_eq!(*endpoint, "Hello");
+        assert_eq!(params, empty_params());
+    }
+
+    #[test]
+    fn ambiguous_router() {
+        let mut router = Router::new();
+
+        router.add("/posts/new", "new");
+        router.add("/posts/:id", "id");
+
+        let (endpoint, params) = router.route("/posts/1").unwrap();
+
+        assert_eq!(*endpoint, "id");
+        assert_eq!(params, one_params("id", "1"));
+
+        let (endpoint, params) = router.route("/posts/new").unwrap();
+
+        assert_eq!(*endpoint, "new");
+        assert_eq!(params, empty_params());
+    }
+
+    #[test]
+    fn ambiguous_router_b() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "id");
+        router.add("/posts/new", "new");
+
+        let (endpoint, params) = router.route("/posts/1").unwrap();
+
+        assert_eq!(*endpoint, "id");
+        assert_eq!(params, one_params("id", "1"));
+
+        let (endpoint, params) = router.route("/posts/new").unwrap();
+
+        assert_eq!(*endpoint, "new");
+        assert_eq!(params, empty_params());
+    }
+
+    #[test]
+    fn ambiguous_router_c() {
+        let mut router = Router::new();
+
+        router.add("/posts/100/comments/10", "100-10");
+        router.add("/posts/:post/comments/100", "post-100");
+
+        let (endpoint, params) = router.route("/posts/100/comments/10").unwrap();
+
+        assert_eq!(*endpoint, "100-10");
+        assert_eq!(params, empty_params());
+
+        let (endpoint, params) = router.route("/posts/100/comments/100").unwrap();
+
+        assert_eq!(*endpoint, "post-100");
+        assert_eq!(params, one_params("post", "100"));
+    }
+
+    #[test]
+    fn multiple_params() {
+        let mut router = Router::new();
+
+        router.add("/posts/:post_id/comments/:comment_id", "comment");
+        router.add("/posts/:post_id/comments", "comments");
+
+        let (endpoint, params) = router.route("/posts/12/comments/100").unwrap();
+        assert_eq!(*endpoint, "comment");
+        assert_eq!(params, two_params("post_id", "12", "comment_id", "100"));
+
+        let (endpoint, params) = router.route("/posts/12/comments").unwrap();
+        assert_eq!(*endpoint, "comments");
+        assert_eq!(params, one_params("post_id", "12"));
+        assert_eq!(params["post_id"], "12".to_string());
+    }
+
+    #[test]
+    fn wildcard_colon() {
+        let mut router = Router::new();
+
+        router.add("/a/*b", "ab");
+        router.add("/a/:b/c", "abc");
+        router.add("/a/:b/c/:d", "abcd");
+
+        let (endpoint, params) = router.route("/a/foo").unwrap();
+        assert_eq!(*endpoint, "ab");
+        assert_eq!(params, one_params("b", "foo"));
+
+        let (endpoint, params) = router.route("/a/foo/bar").unwrap();
+        assert_eq!(*endpoint, "ab");
+        assert_eq!(params, one_params("b", "foo/bar"));
+
+        let (endpoint, params) = router.route("/a/foo/c").unwrap();
+        assert_eq!(*endpoint, "abc");
+        assert_eq!(params, one_params("b", "foo"));
+    }
+
+    #[test]
+    fn unnamed_parameters() {
+        let mut router = Router::new();
+
+        router.add("/foo/:/bar", "test");
+        router.add("/bar/*", "bar");
+        router.add("/hello/*world", "hello");
+
+        let (endpoint, params) = router.route("/foo/test/bar").unwrap();
+        assert_eq!(*endpoint, "test");
+        assert_eq!(params, positional_params(&["test"]));
+
+        let (endpoint, params) = router.route("/bar/hello").unwrap();
+        assert_eq!(*endpoint, "bar");
+        assert_eq!(params, positional_params(&["hello"]));
+
+        let (endpoint, params) = router.route("/hello/world").unwrap();
+        assert_eq!(*endpoint, "hello");
+        assert_eq!(params, one_params("world", "world"));
+    }
+
+    #[test]
+    fn modify_router() {
+        let mut router = Router::new();
+
+        router.add("/a/b/c", "abc");
+        router.add("/e/:f/g", "efg");
+
+        let endpoint = router.route("/a/b/c").unwrap().0;
+        assert_eq!(*endpoint, "abc");
+
+        *router.at_or_default("/a/b/c") = "aabbcc";
+
+        let endpoint = router.route("/a/b/c").unwrap().0;
+        assert_eq!(*endpoint, "aabbcc");
 
         let endpoint = router.route("/e/f/g").unwrap().0;
         assert_eq!(*endpoint, "efg");
 
-        *router.at_or_default("/e/:f/g") = "eeffgg";
+        *router.at_or_default("/e/:f/g") = "eeffgg";
+
+        let endpoint = router.route("/e/f/g").unwrap().0;
+        assert_eq!(*endpoint, "eeffgg");
+    }
+
+    #[test]
+    fn modify_router2() {
+        let mut router: Router<Vec<&str>> = Router::new();
+
+        router.at_or_default("/a/b/c").push("abc");
+        router.at_or_default("/a/b/c").push("aabbcc");
+
+        let endpoint = router.route("/a/b/c").unwrap().0;
+        assert_eq!(*endpoint, vec!["abc", "aabbcc"]);
+
+        router.at_or_default("/a/b/c").clear();
+
+        let endpoint = router.route("/a/b/c").unwrap().0;
+        assert_eq!(*endpoint, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn subtree() {
+        let mut router = Router::new();
+
+        router.add("/v1/posts", "posts1");
+
+        let mut subtree = Router::new();
+
+        subtree.add("/new", "new-post");
+        subtree.add("/edit", "edit-post");
+
+        router.merge("/v1/posts", subtree.clone());
+
+        let endpoint = router.route("/v1/posts").unwrap().0;
+
+        assert_eq!(*endpoint, "posts1");
+
+        let endpoint = router.route("/v1/posts/new").unwrap().0;
+
+        assert_eq!(*endpoint, "new-post");
+
+        router.merge("/v2/posts/", subtree);
+
+        assert_eq!(*router.route("/v2/posts/new").unwrap().0, "new-post");
+        assert_eq!(*router.route("/v2/posts/edit").unwrap().0, "edit-post");
+    }
+
+    #[test]
+    fn try_route_error() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id/comments", "comments");
+
+        assert_eq!(
+            router.try_route("/users").unwrap_err(),
+            RouteError::NotFound
+        );
+        assert_eq!(
+            router.try_route("/posts/1").unwrap_err(),
+            RouteError::NoEndpoint
+        );
+        assert_eq!(
+            router.try_route("/posts").unwrap_err(),
+            RouteError::NoEndpoint
+        );
+        assert!(router.route("/posts").is_none());
+        assert_eq!(
+            *router.try_route("/posts/1/comments").unwrap().0,
+            "comments"
+        );
+
+        let mut router = TreeRouter::new();
+
+        router.add("/posts/:id/comments", "comments");
+
+        assert_eq!(
+            router.try_route("/users").unwrap_err(),
+            RouteError::NotFound
+        );
+        assert_eq!(
+            router.try_route("/posts/1").unwrap_err(),
+            RouteError::NoEndpoint
+        );
+        assert_eq!(
+            *router.try_route("/posts/1/comments").unwrap().0,
+            "comments"
+        );
+    }
+
+    #[test]
+    fn contains_pattern() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id/comments", "comments");
+        router.add("/files/*path", "files");
+
+        assert!(router.contains("/posts/:id/comments"));
+        assert!(router.contains("/files/*path"));
+        assert!(!router.contains("/posts/:id"));
+        assert!(!router.contains("/posts/1/comments"));
+        assert!(!router.contains("/users"));
+        assert!(router.route("/posts/1/comments").is_some());
+
+        let mut router = TreeRouter::new();
+
+        router.add("/posts/:id/comments", "comments");
+        router.add("/files/*path", "files");
+
+        assert!(router.contains("/posts/:id/comments"));
+        assert!(router.contains("/files/*path"));
+        assert!(!router.contains("/posts/:id"));
+        assert!(!router.contains("/posts/1/comments"));
+        assert!(!router.contains("/users"));
+    }
+
+    #[test]
+    fn anonymous_params() {
+        let mut router = Router::new();
+
+        router.add("/a/:/c", "anonymous");
+        router.add("/a/b/c", "static");
+        router.add("/a/:name/d", "named");
+
+        assert_eq!(router.route("/a/b/c").unwrap(), (&"static", empty_params()));
+        assert_eq!(
+            router.route("/a/x/c").unwrap(),
+            (&"anonymous", positional_params(&["x"]))
+        );
+        assert_eq!(
+            router.route("/a/x/d").unwrap(),
+            (&"named", one_params("name", "x"))
+        );
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&"/a/:/c".to_string()));
+        assert!(routes.contains(&"/a/:name/d".to_string()));
+
+        let mut router = Router::new();
+
+        router.add("/a/:name/d", "named");
+        router.add("/a/:/c", "anonymous");
+
+        assert_eq!(
+            router.route("/a/x/c").unwrap(),
+            (&"anonymous", positional_params(&["x"]))
+        );
+        assert_eq!(
+            router.route("/a/x/d").unwrap(),
+            (&"named", one_params("name", "x"))
+        );
+
+        let mut router = TreeRouter::new();
+
+        router.add("/a/:/c", "anonymous");
+        router.add("/a/b/c", "static");
+        router.add("/a/:name/d", "named");
+
+        assert_eq!(router.route("/a/b/c").unwrap(), (&"static", empty_params()));
+        assert_eq!(
+            router.route("/a/x/c").unwrap(),
+            (&"anonymous", positional_params(&["x"]))
+        );
+        assert_eq!(
+            router.route("/a/x/d").unwrap(),
+            (&"named", one_params("name", "x"))
+        );
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&"/a/:/c".to_string()));
+        assert!(routes.contains(&"/a/:name/d".to_string()));
+    }
+
+    #[test]
+    fn escaped_literal_segments() {
+        let mut router = Router::new();
+
+        router.add(r"/files/\:colon-prefixed-literal", "colon");
+        router.add(r"/files/\*", "star");
+        router.add("/files/:name", "file");
+
+        assert_eq!(
+            router.route("/files/:colon-prefixed-literal").unwrap(),
+            (&"colon", empty_params())
+        );
+        assert_eq!(router.route("/files/*").unwrap(), (&"star", empty_params()));
+        assert_eq!(
+            router.route("/files/readme").unwrap(),
+            (&"file", one_params("name", "readme"))
+        );
+        assert!(router.contains(r"/files/\:colon-prefixed-literal"));
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&r"/files/\:colon-prefixed-literal".to_string()));
+        assert!(routes.contains(&r"/files/\*".to_string()));
+
+        let mut router = TreeRouter::new();
+
+        router.add(r"/files/\:colon-prefixed-literal", "colon");
+        router.add("/files/:name", "file");
+
+        assert_eq!(
+            router.route("/files/:colon-prefixed-literal").unwrap(),
+            (&"colon", empty_params())
+        );
+        assert_eq!(
+            router.route("/files/readme").unwrap(),
+            (&"file", one_params("name", "readme"))
+        );
+
+        assert_frozen_agrees(
+            &router,
+            &["/files/:colon-prefixed-literal", "/files/readme"],
+        );
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&r"/files/\:colon-prefixed-literal".to_string()));
+    }
+
+    #[test]
+    fn next_routes() {
+        let mut router = Router::new();
+
+        router.add("/posts/:post_id", "post");
+        router.add("/posts/:post_id/comments", "comments");
+        router.add("/posts/:post_id/:comment_id", "comment");
+        router.add("/posts/:post_id/files/*path", "files");
+
+        assert_eq!(
+            router.next_routes("/posts/5").unwrap(),
+            vec!["comments", "files", ":comment_id"]
+        );
+        assert!(router.next_routes("/posts/5/comments").unwrap().is_empty());
+        assert!(router.next_routes("/posts/5/files/a/b").unwrap().is_empty());
+        assert!(router.next_routes("/posts").is_none());
+        assert!(router.next_routes("/users").is_none());
+    }
+
+    #[test]
+    fn route_budget() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "post");
+        router.add("/:a/:b/:c/d", "abcd");
+        router.add("/:a/*b", "ab");
+
+        assert_eq!(
+            router.route_budgeted("/posts/1", 10).unwrap(),
+            Some((&"post", one_params("id", "1")))
+        );
+        assert_eq!(
+            router.route_budgeted("/posts/1", 10).unwrap(),
+            router.route("/posts/1")
+        );
+        assert_eq!(router.route_budgeted("/users", 10).unwrap(), None);
+
+        let path = "/x/y/z/w";
+        assert!(router.route_budgeted(path, 100).unwrap().is_some());
+        assert_eq!(router.route_budgeted(path, 3), Err(BudgetExceeded));
+    }
+
+    #[test]
+    fn fallback_router() {
+        let mut primary = Router::new();
+        primary.add("/posts/:id", "post");
+
+        let mut fallback = Router::new();
+        fallback.add("/posts/:id", "plugin post");
+        fallback.add("/plugins/:name", "plugin");
+
+        let router = primary.with_fallback_router(fallback);
+
+        assert_eq!(
+            router.route("/posts/1").unwrap(),
+            (&"post", one_params("id", "1"))
+        );
+        assert_eq!(
+            router.route("/plugins/foo").unwrap(),
+            (&"plugin", one_params("name", "foo"))
+        );
+        assert_eq!(router.try_route("/users"), Err(RouteError::NotFound));
+
+        let (mut primary, mut fallback) = router.into_parts();
+        assert!(primary.route("/plugins/foo").is_none());
+        assert!(fallback.route("/plugins/foo").is_some());
+
+        // a redirect of the primary is not lost to the fallback
+        primary.trailing_slash(TrailingSlash::RedirectTo);
+        fallback.add("/posts/:id/", "plugin post slash");
+        let router = primary.with_fallback_router(fallback);
+        assert_eq!(
+            router.try_route("/posts/1/"),
+            Err(RouteError::Redirect("/posts/1".to_string()))
+        );
+    }
+
+    #[test]
+    fn params_find_segments() {
+        let mut router = Router::new();
+
+        router.add("/assets/*path", "assets");
+
+        let (_, params) = router.route("/assets/css/app.css").unwrap();
+        assert_eq!(params.find_segments("path"), Some(vec!["css", "app.css"]));
+        assert_eq!(params.find_segments("missing"), None);
+
+        let (_, params) = router.route("/assets/app.css").unwrap();
+        assert_eq!(params.find_segments("path"), Some(vec!["app.css"]));
+
+        let params = one_params("path", "");
+        assert_eq!(params.find_segments("path"), Some(Vec::new()));
+    }
+
+    #[test]
+    fn route_nth_match() {
+        let mut router = Router::new();
+
+        router.add("/posts/new", "new");
+        router.add("/posts/:id", "post");
+        router.add("/*path", "any");
+
+        assert_eq!(
+            router.route_nth("/posts/new", 0).unwrap(),
+            (&"new", empty_params())
+        );
+        assert_eq!(
+            router.route_nth("/posts/new", 1).unwrap(),
+            (&"post", one_params("id", "new"))
+        );
+        assert_eq!(
+            router.route_nth("/posts/new", 2).unwrap(),
+            (&"any", one_params("path", "posts/new"))
+        );
+        assert!(router.route_nth("/posts/new", 3).is_none());
+        assert_eq!(router.route_nth("/posts/1", 0), router.route("/posts/1"));
+    }
+
+    #[test]
+    fn templatize_path() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "post");
+        router.add("/posts/:id/comments/:cid", "comment");
+        router.add("/posts/new", "new");
+        router.add("/files/*path", "files");
+
+        assert_eq!(router.templatize("/posts/5").unwrap(), "/posts/:id");
+        assert_eq!(
+            router.templatize("/posts/5/comments/9").unwrap(),
+            "/posts/:id/comments/:cid"
+        );
+        assert_eq!(router.templatize("/posts/new").unwrap(), "/posts/new");
+        assert_eq!(
+            router.templatize("/files/css/app.css").unwrap(),
+            "/files/*path"
+        );
+        assert!(router.templatize("/users").is_none());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn constrained_params() {
+        let mut router = Router::new();
+
+        router.add(r"/users/:id(\d+)", "user");
+        router.add("/users/:name", "named");
+        router.add(r"/posts/:id(\d+)", "post");
+        router.add("/posts/me", "me");
+
+        assert_eq!(
+            router.route("/users/42").unwrap(),
+            (&"user", one_params("id", "42"))
+        );
+        assert_eq!(
+            router.route("/users/me").unwrap(),
+            (&"named", one_params("name", "me"))
+        );
+        assert_eq!(
+            router.route("/posts/42").unwrap(),
+            (&"post", one_params("id", "42"))
+        );
+        assert_eq!(router.route("/posts/me").unwrap(), (&"me", empty_params()));
+        assert!(router.route("/posts/new").is_none());
+        assert!(router.contains(r"/users/:id(\d+)"));
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&r"/users/:id(\d+)".to_string()));
+
+        let mut router = TreeRouter::new();
+
+        router.add("/users/:name", "named");
+        router.add(r"/users/:id(\d+)", "user");
+        router.add(r"/posts/:id(\d+)", "post");
+
+        assert_eq!(
+            router.route("/users/42").unwrap(),
+            (&"user", one_params("id", "42"))
+        );
+        assert_eq!(
+            router.route("/users/me").unwrap(),
+            (&"named", one_params("name", "me"))
+        );
+        assert!(router.route("/posts/new").is_none());
+        assert_frozen_agrees(
+            &router,
+            &["/users/42", "/users/me", "/posts/42", "/posts/new"],
+        );
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&r"/users/:id(\d+)".to_string()));
+    }
+
+    #[test]
+    fn route_detailed() {
+        let mut router = TreeRouter::new();
+
+        router.add("/posts/:id", "post");
+        router.add("/posts/:id/comments/:cid", "comment");
+
+        assert_eq!(
+            router.route_detailed("/posts/1"),
+            RouteLookup::Matched(&"post", one_params("id", "1"))
+        );
+        assert_eq!(router.route_detailed("/posts"), RouteLookup::PartialPrefix);
+        assert_eq!(
+            router.route_detailed("/posts/1/comments"),
+            RouteLookup::PartialPrefix
+        );
+        assert_eq!(router.route_detailed("/users"), RouteLookup::NotFound);
+        assert_eq!(
+            router.route_detailed("/posts/1/likes"),
+            RouteLookup::NotFound
+        );
+    }
+
+    #[test]
+    fn reserved_segments() {
+        let mut router = Router::new();
+
+        router.add("/users/:name", "user");
+        router.add("/users/:name/posts", "posts");
+        router.add("/users/new", "new");
+        router.set_reserved_segments(&["admin", "new"]);
+
+        assert_eq!(
+            router.route("/users/tom").unwrap(),
+            (&"user", one_params("name", "tom"))
+        );
+        assert_eq!(
+            router.route("/users/new").unwrap(),
+            (&"new", empty_params())
+        );
+        assert_eq!(router.try_route("/users/admin"), Err(RouteError::NotFound));
+        assert_eq!(
+            router.try_route("/users/admin/posts"),
+            Err(RouteError::NotFound)
+        );
+
+        router.add("/users/admin", "admin");
+        assert_eq!(
+            router.route("/users/admin").unwrap(),
+            (&"admin", empty_params())
+        );
+
+        // reserved words ignore ASCII case along with static segments
+        router.case_insensitive(true);
+        assert_eq!(
+            router.try_route("/users/ADMIN"),
+            router.try_route("/users/admin")
+        );
+        assert_eq!(
+            router.try_route("/users/NEW/posts"),
+            Err(RouteError::NotFound)
+        );
+
+        let mut tree = TreeRouter::new();
+
+        tree.add("/users/:name", "user");
+        tree.add("/users/:name/posts", "posts");
+        tree.add("/users/new", "new");
+        tree.add("/files/*path", "files");
+        tree.set_reserved_segments(&["admin", "new"]);
+
+        assert_eq!(
+            tree.route("/users/tom").unwrap(),
+            (&"user", one_params("name", "tom"))
+        );
+        assert_eq!(*tree.route("/users/new").unwrap().0, "new");
+        assert_eq!(tree.try_route("/users/admin"), Err(RouteError::NotFound));
+        assert_eq!(
+            tree.try_route("/users/admin/posts"),
+            Err(RouteError::NotFound)
+        );
+        assert_eq!(
+            tree.route("/files/admin").unwrap(),
+            (&"files", one_params("path", "admin"))
+        );
+        assert_eq!(
+            tree.try_route("/users/Admin"),
+            Ok((&"user", one_params("name", "Admin")))
+        );
+        assert_frozen_agrees(&tree, &["/users/admin", "/users/new", "/users/tom"]);
+
+        tree.case_insensitive(true);
+        assert_eq!(tree.try_route("/users/Admin"), Err(RouteError::NotFound));
+        assert_eq!(
+            tree.try_route("/users/NEW/posts"),
+            Err(RouteError::NotFound)
+        );
+    }
+
+    #[test]
+    fn optional_trailing_param() {
+        let mut router = TreeRouter::new();
+
+        router.add("/files/:name?", "files");
+        router.add("/posts/:id", "post");
+
+        assert_eq!(
+            router.route("/files/readme").unwrap(),
+            (&"files", one_params("name", "readme"))
+        );
+        assert_eq!(router.route("/files").unwrap(), (&"files", empty_params()));
+        assert!(router.route("/posts").is_none());
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&"/files/:name?".to_string()));
+
+        router.add("/files", "index");
+        assert_eq!(router.route("/files").unwrap(), (&"index", empty_params()));
+        assert_frozen_agrees(&router, &["/files/readme", "/files", "/posts", "/posts/1"]);
+    }
+
+    #[test]
+    fn optional_param_after_param() {
+        let mut router = TreeRouter::new();
+
+        router.add("/items/:id/:variant?", "item");
+
+        let (endpoint, params) = router.route("/items/42").unwrap();
+        assert_eq!(*endpoint, "item");
+        assert_eq!(params, one_params("id", "42"));
+        assert!(!params.contains_key("variant"));
+
+        let (endpoint, params) = router.route("/items/42/red").unwrap();
+        assert_eq!(*endpoint, "item");
+        assert_eq!(params, two_params("id", "42", "variant", "red"));
+
+        assert!(router.route("/items").is_none());
+        assert!(router.route("/items/42/red/large").is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn optional_wildcard() {
+        let mut router = TreeRouter::new();
+
+        router.add("/files/*path?", "files");
+    }
+
+    #[test]
+    fn route_match_pattern() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "post");
+        router.add("/posts/:/raw", "raw");
+        router.add("/files/*", "files");
+
+        let found = router.route_match("/posts/42").unwrap();
+        assert_eq!(*found.endpoint, "post");
+        assert_eq!(found.params, one_params("id", "42"));
+        assert_eq!(found.pattern, "/posts/:id");
+        assert_eq!(
+            router.route_match("/posts/42/raw").unwrap().pattern,
+            "/posts/:/raw"
+        );
+        assert_eq!(
+            router.route_match("/files/a/b").unwrap().pattern,
+            "/files/*"
+        );
+        assert!(router.route_match("/files/a/b").unwrap().matched_wildcard());
+        assert!(!router.route_match("/posts/42").unwrap().matched_wildcard());
+        assert!(router.route_match("/users").is_none());
+        assert!(router.route("/files/a/b").unwrap().1.matched_wildcard());
+        assert!(!router.route("/posts/42").unwrap().1.matched_wildcard());
+
+        router.add("/posts/\\:id", "literal");
+        router.add("/about", "about");
+        let found = router.route_match("/posts/:id").unwrap();
+        assert_eq!(
+            (*found.endpoint, found.kind),
+            ("literal", MatchKind::Static)
+        );
+        assert_eq!(
+            router.route_match("/about").unwrap().kind,
+            MatchKind::Static
+        );
+
+        let mut router = TreeRouter::new();
+
+        router.add("/posts/:id", "post");
+        router.add("/posts/:/raw", "raw");
+        router.add("/files/*", "files");
+
+        let found = router.route_match("/posts/42").unwrap();
+        assert_eq!(*found.endpoint, "post");
+        assert_eq!(found.params, one_params("id", "42"));
+        assert_eq!(found.pattern, "/posts/:id");
+        assert_eq!(
+            router.route_match("/posts/42/raw").unwrap().pattern,
+            "/posts/:/raw"
+        );
+        assert_eq!(
+            router.route_match("/files/a/b").unwrap().pattern,
+            "/files/*"
+        );
+        assert!(router.route_match("/files/a/b").unwrap().matched_wildcard());
+        assert!(!router.route_match("/posts/42").unwrap().matched_wildcard());
+        assert!(router.route_match("/users").is_none());
+
+        // shared edges are named by the matched route
+        let routes = [
+            ("/posts/:id", "post"),
+            ("/posts/:post_id/comments/:id", "comment"),
+            ("/posts/:post_id/*rest", "rest"),
+        ];
+        let nfa: Router<_> = routes.into_iter().collect();
+        let tree: TreeRouter<_> = routes.into_iter().collect();
+
+        for (path, pattern) in [
+            ("/posts/1", "/posts/:id"),
+            ("/posts/1/comments/2", "/posts/:post_id/comments/:id"),
+            ("/posts/1/comments", "/posts/:post_id/*rest"),
+        ] {
+            assert_eq!(nfa.route_match(path).unwrap().pattern, pattern);
+            assert_eq!(tree.route_match(path).unwrap().pattern, pattern);
+        }
+
+        for (path, kind) in [
+            ("/posts/1", MatchKind::Param),
+            ("/posts/1/comments/2", MatchKind::Param),
+            ("/posts/1/comments", MatchKind::Wildcard),
+            ("/posts/1/", MatchKind::Wildcard),
+        ] {
+            assert_eq!(
+                nfa.route_match(path).unwrap().kind,
+                kind,
+                "routing `{path}`"
+            );
+            assert_eq!(
+                tree.route_match(path).unwrap().kind,
+                kind,
+                "routing `{path}`"
+            );
+            assert_eq!(nfa.route(path).unwrap().1.kind(), kind, "routing `{path}`");
+            assert_eq!(tree.route(path).unwrap().1.kind(), kind, "routing `{path}`");
+        }
+
+        let (endpoint, params, pattern) =
+            nfa.route_match("/posts/1/comments/2").unwrap().into_parts();
+        assert_eq!(*endpoint, "comment");
+        assert_eq!(params, two_params("post_id", "1", "id", "2"));
+        assert_eq!(pattern, "/posts/:post_id/comments/:id");
+    }
+
+    #[test]
+    fn route_annotate() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id/comments/:cid", "comment");
+        router.add("/files/*path", "files");
+
+        let target = "/posts/5/comments/19?sort=desc";
+        let (endpoint, params) = router.route_annotate(target).unwrap();
+        assert_eq!(*endpoint, "comment");
+        assert_eq!(params, vec![("id", 7..8), ("cid", 18..20)]);
+        assert_eq!(&target[params[0].1.clone()], "5");
+        assert_eq!(&target[params[1].1.clone()], "19");
+
+        let target = "/files/css/app.css";
+        let (_, params) = router.route_annotate(target).unwrap();
+        assert_eq!(&target[params[0].1.clone()], "css/app.css");
+
+        assert!(router.route_annotate("/users?id=1").is_none());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn constrained_params_fallthrough() {
+        let mut router = Router::new();
+
+        router.add(r"/posts/:id(\d+)", "id");
+        router.add("/posts/:slug", "slug");
+
+        assert_eq!(
+            router.route("/posts/1").unwrap(),
+            (&"id", one_params("id", "1"))
+        );
+        assert_eq!(
+            router.route("/posts/abc").unwrap(),
+            (&"slug", one_params("slug", "abc"))
+        );
+
+        let err = router.try_add(r"/users/:id([a-)", "user").unwrap_err();
+        assert!(matches!(err, AddError::InvalidConstraint(_)));
+        assert!(err.conflict().is_none());
+        assert!(router.route("/users/1").is_none());
+    }
+
+    #[test]
+    fn contains_merged_pattern() {
+        let mut api = Router::new();
+        api.add("/posts/:id", "post");
+
+        let mut router = Router::new();
+        router.add("/", "index");
+        router.merge("/api/v1", api);
+
+        assert!(router.contains("/api/v1/posts/:id"));
+        assert!(!router.contains("/api/v1/posts"));
+        assert!(!router.contains("/api/v1"));
+        assert!(!router.contains("/api"));
+
+        let mut api = TreeRouter::new();
+        api.add("/posts/:id", "post");
+
+        let mut router = TreeRouter::new();
+        router.add("/", "index");
+        router.merge("/api", api);
+
+        assert!(router.contains("/api/posts/:id"));
+        assert!(!router.contains("/api/posts"));
+        assert!(!router.contains("/api"));
+        assert_eq!(
+            router.route("/api/posts/1").unwrap(),
+            (&"post", one_params("id", "1"))
+        );
+    }
+
+    #[test]
+    fn typed_params() {
+        let mut router = Router::new();
+
+        router.add("/users/:id<int>", "id");
+        router.add("/users/:name<alpha>", "name");
+        router.add("/orders/:id<uuid>", "order");
+
+        assert_eq!(
+            router.route("/users/42").unwrap(),
+            (&"id", one_params("id", "42"))
+        );
+        assert_eq!(
+            router.route("/users/bob").unwrap(),
+            (&"name", one_params("name", "bob"))
+        );
+        assert!(router.route("/users/bob42").is_none());
+        assert!(router
+            .route("/orders/67e55044-10b1-426f-9247-bb680e5fe0c8")
+            .is_some());
+        assert!(router.route("/orders/67e55044").is_none());
+        assert!(router.contains("/users/:id<int>"));
+
+        let err = router.try_add("/posts/:id<float>", "post").unwrap_err();
+        assert_eq!(
+            err,
+            AddError::InvalidConstraint("unknown param kind `float`".to_string())
+        );
+
+        let mut router = TreeRouter::new();
+
+        router.add("/users/:id<int>", "id");
+        router.add("/users/:name<alpha>", "name");
+
+        assert_eq!(
+            router.route("/users/42").unwrap(),
+            (&"id", one_params("id", "42"))
+        );
+        assert_eq!(
+            router.route("/users/bob").unwrap(),
+            (&"name", one_params("name", "bob"))
+        );
+        assert!(router.route("/users/bob42").is_none());
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&"/users/:id<int>".to_string()));
+    }
+
+    #[test]
+    fn get_by_pattern() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "post");
+        router.add("/posts/new", "new");
+
+        assert_eq!(router.get("/posts/:id"), Some(&"post"));
+        assert_eq!(router.get("/posts/:name"), Some(&"post"));
+        assert_eq!(router.get("/posts/new"), Some(&"new"));
+        assert_eq!(router.get("/posts/42"), None);
+        assert_eq!(router.get("/posts"), None);
+
+        *router.get_mut("/posts/:id").unwrap() = "updated";
+        assert_eq!(*router.route("/posts/42").unwrap().0, "updated");
+        assert!(router.get_mut("/users").is_none());
+    }
+
+    #[test]
+    fn escaped_literal_markers() {
+        let mut router = TreeRouter::new();
+
+        router.add(r"/files/\:", "colon");
+        router.add(r"/files/\*", "star");
+        router.add("/files/:name", "file");
+        router.add(r"/files/\:readme", "readme");
+
+        assert_eq!(
+            router.route("/files/:").unwrap(),
+            (&"colon", empty_params())
+        );
+        assert_eq!(router.route("/files/*").unwrap(), (&"star", empty_params()));
+        assert_eq!(
+            router.route("/files/:readme").unwrap(),
+            (&"readme", empty_params())
+        );
+        assert_eq!(
+            router.route("/files/readme").unwrap(),
+            (&"file", one_params("name", "readme"))
+        );
+        assert!(router.contains("/files/:name"));
+        assert!(router.contains(r"/files/\:"));
+
+        let mut router = Router::new();
+
+        router.add(r"/files/\:", "colon");
+        router.add("/files/:name", "file");
+
+        assert_eq!(
+            router.route("/files/:").unwrap(),
+            (&"colon", empty_params())
+        );
+        assert_eq!(
+            router.route("/files/readme").unwrap(),
+            (&"file", one_params("name", "readme"))
+        );
+    }
+
+    #[test]
+    fn params_get_and_expect() {
+        let params = one_params("id", "42");
+
+        assert_eq!(params.get("id"), Some("42"));
+        assert_eq!(params.get("name"), None);
+        assert_eq!(params.expect("id", "route has id"), "42");
+        assert_eq!(&params["id"], "42");
+
+        assert_eq!(params.find_or("id", "0"), "42");
+        assert_eq!(params.find_or("name", "anonymous"), "anonymous");
+    }
+
+    #[test]
+    #[should_panic(expected = "route has name: params[name] did not exist")]
+    fn params_expect_missing() {
+        let params = one_params("id", "42");
+
+        params.expect("name", "route has name");
+    }
+
+    #[test]
+    fn try_merge_collisions() {
+        let mut router = Router::new();
+        router.add("/api/health", "health");
+        router.add("/api/users/:id", "user");
+
+        let mut other = Router::new();
+        other.add("/health", "other health");
+        other.add("/users/:name", "other user");
+        other.add("/posts", "posts");
+
+        let mut collisions = router.try_merge("/api", other.clone()).unwrap_err();
+        collisions.sort();
+        assert_eq!(collisions, vec!["/api/health", "/api/users/:name"]);
+        assert_eq!(*router.route("/api/health").unwrap().0, "health");
+        assert!(router.route("/api/posts").is_none());
+
+        assert!(router.try_merge("/v2", other).is_ok());
+        assert_eq!(*router.route("/v2/health").unwrap().0, "other health");
+
+        let mut router = TreeRouter::new();
+        router.add("/api/health", "health");
+
+        let mut other = TreeRouter::new();
+        other.add("/health", "other health");
+        other.add("/posts", "posts");
+
+        assert_eq!(
+            router.try_merge("/api", other.clone()).unwrap_err(),
+            vec!["/api/health"]
+        );
+        assert!(router.route("/api/posts").is_none());
+
+        assert!(router.try_merge("/v2", other.clone()).is_ok());
+        assert_eq!(*router.route("/v2/posts").unwrap().0, "posts");
+
+        // a plain merge overwrites instead
+        router.merge("/api", other);
+        assert_eq!(*router.route("/api/health").unwrap().0, "other health");
+        assert_eq!(*router.route("/api/posts").unwrap().0, "posts");
+    }
+
+    #[test]
+    fn merge_mapped_patterns() {
+        let routes = [
+            ("/users/:id", 1),
+            ("/health", 2),
+            ("/users/:id/posts/*rest", 3),
+            ("/", 4),
+        ];
+
+        let mut router = Router::new();
+        router.add("/api/health", 0);
+        let other: Router<_> = routes.into_iter().collect();
+        let listed: Vec<String> = other.routes().map(|(p, _)| p).collect();
+
+        let mapped = router.merge_mapped("/api/", other);
+        assert_eq!(
+            mapped
+                .iter()
+                .map(|(old, _)| old.clone())
+                .collect::<Vec<_>>(),
+            listed
+        );
+        for (old, new) in &mapped {
+            assert_eq!(*new, format!("/api{old}"));
+            assert!(router.contains(new));
+        }
+        assert_eq!(*router.route("/api/health").unwrap().0, 2);
+
+        let mut router = TreeRouter::new();
+        router.add("/api/health", 0);
+        let other: TreeRouter<_> = routes.into_iter().collect();
+        let listed: Vec<String> = other.routes().map(|(p, _)| p).collect();
+
+        let mapped = router.merge_mapped("/api/", other);
+        assert_eq!(
+            mapped
+                .iter()
+                .map(|(old, _)| old.clone())
+                .collect::<Vec<_>>(),
+            listed
+        );
+        for (old, new) in &mapped {
+            assert_eq!(*new, format!("/api{old}"));
+            assert!(router.contains(new));
+        }
+        assert_eq!(*router.route("/api/health").unwrap().0, 2);
+    }
+
+    #[test]
+    fn static_prefix_falls_through() {
+        let mut router = Router::new();
+
+        router.add("/posts/new", "new");
+
+        assert_eq!(router.try_route("/posts"), Err(RouteError::NoEndpoint));
+
+        router.add("/:name", "name");
+
+        assert_eq!(
+            router.route("/posts").unwrap(),
+            (&"name", one_params("name", "posts"))
+        );
+        assert_eq!(
+            router.route("/posts/new").unwrap(),
+            (&"new", empty_params())
+        );
+    }
+
+    #[test]
+    fn nested_wildcards() {
+        let mut router = TreeRouter::new();
+
+        router.add("/a/*x", "x");
+        router.add("/a/b/*y", "y");
+        router.add("/*z", "z");
+        router.add("/a/b/c/e", "e");
+
+        assert_eq!(
+            router.route("/a/b/c/d").unwrap(),
+            (&"y", one_params("y", "c/d"))
+        );
+        assert_eq!(
+            router.route("/a/b/c").unwrap(),
+            (&"y", one_params("y", "c"))
+        );
+        assert_eq!(
+            router.route("/a/c/d").unwrap(),
+            (&"x", one_params("x", "c/d"))
+        );
+        assert_eq!(
+            router.route("/b/c").unwrap(),
+            (&"z", one_params("z", "b/c"))
+        );
+        assert_eq!(router.route("/a/b/c/e").unwrap(), (&"e", empty_params()));
+        assert_frozen_agrees(
+            &router,
+            &["/a/b/c/d", "/a/b/c", "/a/c/d", "/b/c", "/a/b/c/e"],
+        );
+
+        let mut router = TreeRouter::new();
+
+        router.add("/a/*x", "x");
+        router.add("/*z", "z");
+        router.add("/a/b/c/e", "e");
+
+        // deepest wildcard ancestor wins, even when an ancestor in between has none
+        assert_eq!(
+            router.route("/a/b/c/d").unwrap(),
+            (&"x", one_params("x", "b/c/d"))
+        );
+        assert_eq!(
+            router.route("/b/c/f").unwrap(),
+            (&"z", one_params("z", "b/c/f"))
+        );
+        assert_frozen_agrees(&router, &["/a/b/c/d", "/b/c/f", "/a/b/c/e"]);
+    }
+
+    #[test]
+    fn tree_trailing_slash_policy() {
+        let mut router = TreeRouter::new();
+
+        router.add("/a", "a");
+        router.add("/b/", "b");
+        router.add("/", "root");
+        router.add("/c/:id", "c");
+
+        assert!(router.route("/a/").is_none());
+
+        router.trailing_slash(TrailingSlash::Ignore);
+
+        assert_eq!(*router.route("/a").unwrap().0, "a");
+        assert_eq!(*router.route("/a/").unwrap().0, "a");
+        assert_eq!(*router.route("/b").unwrap().0, "b");
+        assert_eq!(*router.route("/").unwrap().0, "root");
+        assert_eq!(
+            router.route("/c/1/").unwrap(),
+            (&"c", one_params("id", "1"))
+        );
+        assert!(router.route("/missing/").is_none());
+        assert_frozen_agrees(&router, &["/a", "/a/", "/b", "/", "/c/1/", "/missing/"]);
+
+        router.trailing_slash(TrailingSlash::RedirectTo);
+
+        assert_eq!(*router.route("/a").unwrap().0, "a");
+        assert_eq!(
+            router.try_route("/a/").unwrap_err(),
+            RouteError::Redirect("/a".to_string())
+        );
+        assert_eq!(
+            router.try_route("/b").unwrap_err(),
+            RouteError::Redirect("/b/".to_string())
+        );
+        assert_eq!(*router.route("/").unwrap().0, "root");
+        assert_frozen_agrees(&router, &["/a", "/a/", "/b", "/b/", "/"]);
+    }
+
+    #[test]
+    fn collect_routes() {
+        let routes = vec![
+            ("/posts", "index"),
+            ("/posts/:id", "show"),
+            ("/files/*path", "files"),
+            ("/posts", "latest"),
+        ];
+
+        let mut router: Router<_> = routes.clone().into_iter().collect();
+
+        assert_eq!(*router.route("/posts").unwrap().0, "latest");
+        assert_eq!(
+            router.route("/posts/1").unwrap(),
+            (&"show", one_params("id", "1"))
+        );
+        assert_eq!(
+            router.route("/files/a/b").unwrap(),
+            (&"files", one_params("path", "a/b"))
+        );
+
+        router.extend(vec![("/users/:id".to_string(), "user")]);
+        assert_eq!(*router.route("/users/1").unwrap().0, "user");
+
+        let mut router: TreeRouter<_> = routes.into_iter().collect();
+
+        assert_eq!(*router.route("/posts").unwrap().0, "latest");
+        assert_eq!(
+            router.route("/posts/1").unwrap(),
+            (&"show", one_params("id", "1"))
+        );
+
+        router.extend(vec![("/users/:id".to_string(), "user")]);
+        assert_eq!(*router.route("/users/1").unwrap().0, "user");
+    }
+
+    #[test]
+    fn routers_agree() {
+        let patterns = [
+            "/",
+            "/a/:b",
+            "/a/:b/c",
+            "/files/*path",
+            "/x/:y/*z",
+            "/s/static",
+        ];
+        let paths = [
+            "/",
+            "/a/1",
+            "/a/",
+            "/a/1/c",
+            "/a//c",
+            "//a/1",
+            "/files",
+            "/files/",
+            "/files/a/b",
+            "/x/1",
+            "/x/1/2/3",
+            "/s/static/",
+            "/missing",
+        ];
+
+        let nfa: Router<_> = patterns.iter().map(|p| (*p, *p)).collect();
+        let tree: TreeRouter<_> = patterns.iter().map(|p| (*p, *p)).collect();
+
+        for path in paths {
+            assert_eq!(nfa.route(path), tree.route(path), "routing `{path}`");
+        }
+        assert_frozen_agrees(&tree, &paths);
+
+        // both backtrack out of a static segment
+        let routes = [("/a/b", "b"), ("/a/:c/d", "d")];
+        assert_routers_agree(&routes, "/a/b/d", Some((&"d", one_params("c", "b"))));
+        assert_routers_agree(&routes, "/a/b", Some((&"b", empty_params())));
+        assert_routers_agree(&routes, "/a/c/d", Some((&"d", one_params("c", "c"))));
+
+        // both rank more segments over an earlier wildcard, and a deeper wildcard first
+        let routes = [("/a/*w", "w"), ("/:x/b/c", "c"), ("/:x/d/*w", "dw")];
+        for (path, expected) in [
+            ("/a/b/c", Some((&"c", one_params("x", "a")))),
+            ("/a/b/d", Some((&"w", one_params("w", "b/d")))),
+            ("/a/d/e/f", Some((&"dw", two_params("x", "a", "w", "e/f")))),
+            ("/a/d", Some((&"w", one_params("w", "d")))),
+            ("/b/d", None),
+        ] {
+            assert_routers_agree(&routes, path, expected);
+        }
+    }
+
+    #[test]
+    fn root_path() {
+        let routes = [("/:a", "param"), ("/", "root"), ("/*w", "wildcard")];
+
+        for path in ["/", "", "//"] {
+            assert_routers_agree(&routes, path, Some((&"root", empty_params())));
+        }
+        assert_routers_agree(&routes, "/x", Some((&"param", one_params("a", "x"))));
+
+        // the empty pattern is the root route as well
+        let mut nfa = Router::new();
+        nfa.add("", "empty");
+        assert_eq!(*nfa.route("/").unwrap().0, "empty");
+        assert!(nfa.contains("/"));
+
+        let mut tree = TreeRouter::new();
+        tree.add("", "empty");
+        assert_eq!(*tree.route("/").unwrap().0, "empty");
+        assert!(tree.contains("/"));
+
+        // without a root route, `/` is an empty param or wildcard
+        let routes = [("/*w", "wildcard")];
+        assert_routers_agree(&routes, "/", Some((&"wildcard", one_params("w", ""))));
+
+        // the root is independent of static routes, whichever is added first
+        let routes = [("/foo", "foo"), ("/", "root")];
+        assert_routers_agree(&routes, "", Some((&"root", empty_params())));
+        assert_routers_agree(&routes, "/foo/", None);
+
+        let mut nfa: Router<_> = routes.into_iter().collect();
+        let mut tree: TreeRouter<_> = routes.into_iter().collect();
+        assert_eq!(nfa.remove("/"), Some("root"));
+        assert_eq!(tree.remove("/"), Some("root"));
+        assert_routed_alike(&nfa, &tree, "/", None);
+        assert_routed_alike(&nfa, &tree, "/foo", Some((&"foo", empty_params())));
+    }
+
+    #[test]
+    fn tree_conflicting_routes() {
+        let mut router = TreeRouter::new();
+
+        router.try_add("/posts/:id", "post").unwrap();
+        router.try_add("/files/*path", "files").unwrap();
+
+        let err = router.try_add("/posts/:id", "again").unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/posts/:id");
+        assert_eq!(err.to_string(), "route conflicts with `/posts/:id`");
+
+        let err = router.try_add("/posts/:post_id", "renamed").unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/posts/:id");
+        let err = router
+            .try_add("/posts/:post_id/comments", "comments")
+            .unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/posts/:id");
+        let err = router.try_add("/files/*rest", "rest").unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/files/*path");
+        assert!(matches!(
+            router.try_add("/users/:id<number>", "user"),
+            Err(AddError::InvalidConstraint(_))
+        ));
+
+        // the original routes are kept
+        assert_eq!(*router.route("/posts/1").unwrap().0, "post");
+        assert_eq!(*router.route("/files/a").unwrap().0, "files");
+
+        router.try_add("/posts/:id/comments", "comments").unwrap();
+        router.try_add("/tags/:tag<alpha>", "tag").unwrap();
+        router.try_add("/users/:id?", "user").unwrap();
+        assert_eq!(*router.route("/posts/1/comments").unwrap().0, "comments");
+
+        // `add` overwrites
+        router.add("/posts/:id", "again");
+        assert_eq!(*router.route("/posts/1").unwrap().0, "again");
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert_eq!(validate_pattern("/"), Ok(()));
+        assert_eq!(validate_pattern("/posts/"), Ok(()));
+        assert_eq!(validate_pattern("/posts/:id<int>/*rest"), Ok(()));
+        assert_eq!(
+            validate_pattern("/users/:id?"),
+            Err(PatternError::InvalidName {
+                index: 1,
+                segment: ":id?".to_string()
+            })
+        );
+        assert_eq!(validate_pattern("/a/:/\\*b"), Ok(()));
+
+        assert_eq!(
+            validate_pattern("/*wild/more"),
+            Err(PatternError::WildcardNotLast {
+                index: 0,
+                segment: "*wild".to_string()
+            })
+        );
+        assert_eq!(
+            validate_pattern("/a//b"),
+            Err(PatternError::EmptySegment { index: 1 })
+        );
+        assert_eq!(
+            validate_pattern("/a/:b:c").unwrap_err().to_string(),
+            "name of segment 1 `:b:c` has a reserved char"
+        );
+        assert_eq!(validate_pattern("/a/*b?").unwrap_err().index(), 1);
+
+        assert_eq!(validate_pattern("/a/:id/b/:post_id/*rest"), Ok(()));
+        assert_eq!(validate_pattern("/a/:/b/:/*"), Ok(()));
+        assert_eq!(
+            validate_pattern("/a/:id/b/:id"),
+            Err(PatternError::DuplicateName {
+                index: 3,
+                segment: ":id".to_string()
+            })
+        );
+        assert_eq!(
+            validate_pattern("/a/:id<int>/*id").unwrap_err().to_string(),
+            "name of segment 2 `*id` is already used"
+        );
+        assert!(matches!(
+            validate_pattern("/a/:b*c"),
+            Err(PatternError::InvalidName { index: 1, .. })
+        ));
+
+        let mut router = Router::new();
+        let err = router.try_add("/files/*path/more", "files").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wildcard `*path` of segment 1 is not the last segment"
+        );
+        assert!(router.is_empty());
+
+        // optional params are only known to the tree
+        for pattern in ["/files/:name?", "/files/:id<int>?"] {
+            assert!(matches!(
+                router.try_add(pattern, "files"),
+                Err(AddError::InvalidPattern(PatternError::InvalidName {
+                    index: 1,
+                    ..
+                }))
+            ));
+        }
+        assert!(router.is_empty());
+        TreeRouter::new().try_add("/files/:name?", "files").unwrap();
+
+        let mut router = TreeRouter::new();
+        assert!(matches!(
+            router.try_add("/a//b", "a"),
+            Err(AddError::InvalidPattern(PatternError::EmptySegment {
+                index: 1
+            }))
+        ));
+        assert!(router.route("/a//b").is_none());
+        assert!(matches!(
+            router.try_add("/a/:id/b/:id", "a"),
+            Err(AddError::InvalidPattern(PatternError::DuplicateName { .. }))
+        ));
+        router.try_add("/a/:id/b/:b_id", "a").unwrap();
+        assert_eq!(
+            router.route("/a/1/b/2").unwrap().1,
+            two_params("id", "1", "b_id", "2")
+        );
+    }
+
+    #[test]
+    fn multiple_wildcards() {
+        assert!(matches!(
+            validate_pattern("/a/*x/b/*y"),
+            Err(PatternError::WildcardNotLast { index: 1, .. })
+        ));
+
+        let mut router = Router::new();
+        assert!(matches!(
+            router.try_add("/files/*path/extra", "extra"),
+            Err(AddError::InvalidPattern(PatternError::WildcardNotLast {
+                index: 1,
+                ..
+            }))
+        ));
+        router.try_add("/files/*path", "files").unwrap();
+        assert_eq!(
+            router.route("/files/a/extra").unwrap(),
+            (&"files", one_params("path", "a/extra"))
+        );
+
+        let mut router = TreeRouter::new();
+        assert!(router.try_add("/a/*x/b/*y", "xy").is_err());
+        router.try_add("/files/*path", "files").unwrap();
+        assert_eq!(
+            router.route("/files/a/extra").unwrap(),
+            (&"files", one_params("path", "a/extra"))
+        );
+    }
+
+    #[test]
+    fn router_stats() {
+        let patterns = [
+            ("/posts", "posts"),
+            ("/posts/:id", "post"),
+            ("/posts/:id/comments/:cid<int>", "comment"),
+            ("/files/*path", "files"),
+        ];
+
+        assert_eq!(
+            Router::<()>::new().stats(),
+            RouterStats {
+                state_count: 1,
+                ..Default::default()
+            }
+        );
+
+        let router: Router<_> = patterns.into_iter().collect();
+        assert_eq!(
+            router.stats(),
+            RouterStats {
+                state_count: 7,
+                accepting_count: 4,
+                max_depth: 4,
+                param_edge_count: 2,
+            }
+        );
+
+        let router: TreeRouter<_> = patterns.into_iter().collect();
+        assert_eq!(
+            router.stats(),
+            RouterStats {
+                state_count: 7,
+                accepting_count: 4,
+                max_depth: 4,
+                param_edge_count: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn tree_routes() {
+        let mut router = TreeRouter::new();
+
+        router.add("/posts/:post_id", "post");
+        router.add("/posts/:id/comments/:comment_id<int>", "comment");
+        router.add("/files/*path", "files");
+        router.add("/users/:name?", "user");
+        router.add("/\\:literal", "literal");
+        router.add("/", "root");
+
+        let mut routes: Vec<_> = router.routes().collect();
+        routes.sort();
+        assert_eq!(
+            routes,
+            vec![
+                ("/".to_string(), &"root"),
+                ("/\\:literal".to_string(), &"literal"),
+                ("/files/*path".to_string(), &"files"),
+                (
+                    "/posts/:id/comments/:comment_id<int>".to_string(),
+                    &"comment"
+                ),
+                ("/posts/:post_id".to_string(), &"post"),
+                ("/users/:name?".to_string(), &"user"),
+            ]
+        );
+
+        // reconstructed patterns lead to the same routes
+        for (pattern, _) in routes {
+            assert!(router.contains(&pattern), "contains `{pattern}`");
+        }
+    }
+
+    #[test]
+    fn route_segments() {
+        let router: Router<_> = [
+            ("/", "root"),
+            ("/posts/:id", "post"),
+            ("/posts/:id/comments", "comments"),
+            ("/files/*path", "files"),
+            ("/static/about", "about"),
+        ]
+        .into_iter()
+        .collect();
+
+        let cases: [&[&str]; 10] = [
+            &[],
+            &[""],
+            &["", ""],
+            &["posts", "1"],
+            &["", "posts", "1", "comments"],
+            &["files"],
+            &["files", "a"],
+            &["files", "a", "b", ""],
+            &["static", "about"],
+            &["missing", "x"],
+        ];
+        for segments in cases {
+            assert_eq!(
+                router.route_segments(segments),
+                router.route(&segments.join("/")),
+                "routing {segments:?}"
+            );
+        }
+
+        assert_eq!(
+            router.route_segments(&["files", "a", "b"]).unwrap(),
+            (&"files", one_params("path", "a/b"))
+        );
+        assert!(router.route_segments(&["missing"]).is_none());
+    }
+
+    #[test]
+    fn route_count() {
+        let routes = [
+            ("/posts", "posts"),
+            ("/posts/:id", "post"),
+            ("/files/*path", "files"),
+        ];
+
+        let mut router: Router<_> = routes.into_iter().collect();
+        assert_eq!(router.len(), 3);
+        router.add("/posts", "again");
+        assert_eq!(router.len(), 3);
+        router.remove("/posts/:id");
+        assert_eq!(router.len(), 2);
+        router.clear();
+        assert_eq!(router.len(), 0);
+        assert!(router.is_empty());
+
+        let mut router = TreeRouter::new();
+        assert!(router.is_empty());
+        router.extend(routes);
+        assert_eq!(router.len(), 3);
+        assert!(!router.is_empty());
+        router.add("/posts", "again");
+        assert_eq!(router.len(), 3);
+        router.remove("/posts/:id");
+        assert_eq!(router.len(), 2);
+        router.clear();
+        assert!(router.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_params() {
+        let params = two_params("post_id", "1", "name", "a b");
+
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(json, r#"{"name":"a b","post_id":"1"}"#);
+
+        let back: Params = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, params);
+        assert_eq!(serde_json::to_string(&Params::new()).unwrap(), "{}");
+
+        // only named params are part of the map
+        let router: Router<_> = [("/:id/*", ())].into_iter().collect();
+        let (_, params) = router.route("/7/a/b").unwrap();
+        assert_eq!(serde_json::to_string(&params).unwrap(), r#"{"id":"7"}"#);
+
+        assert!(serde_json::from_str::<Params>(r#"{"id":7}"#).is_err());
+        assert!(serde_json::from_str::<Params>(r#"["id"]"#).is_err());
+    }
+
+    #[test]
+    fn router_entry() {
+        struct Handler {
+            name: &'static str,
+            hits: usize,
+        }
+
+        let mut router = Router::new();
+
+        let handler = router.entry("/posts/:id").or_insert(Handler {
+            name: "post",
+            hits: 0,
+        });
+        handler.hits += 1;
+        assert_eq!(router.len(), 1);
+
+        // an existing endpoint is kept
+        let handler = router
+            .entry("/posts/:id")
+            .and_modify(|h| h.hits += 1)
+            .or_insert_with(|| unreachable!());
+        assert_eq!((handler.name, handler.hits), ("post", 2));
+
+        // nothing is added until an endpoint is inserted
+        let entry = router.entry("/users/:id").and_modify(|h| h.hits += 1);
+        assert_eq!(entry.pattern(), "/users/:id");
+        assert!(!router.contains("/users/:id"));
+        assert!(router.route("/users/1").is_none());
+
+        router.entry("/users/:id").or_insert_with(|| Handler {
+            name: "user",
+            hits: 0,
+        });
+        let (handler, params) = router.route("/users/1").unwrap();
+        assert_eq!(handler.name, "user");
+        assert_eq!(params, one_params("id", "1"));
+
+        let mut router: Router<Vec<&str>> = Router::new();
+        router.entry("/a").or_default().push("x");
+        router.entry("/a").or_default().push("y");
+        assert_eq!(router.get("/a"), Some(&vec!["x", "y"]));
+    }
+
+    #[test]
+    fn custom_separator() {
+        let routes = [
+            ("com.example.service.:method", "method"),
+            ("com.example.health", "health"),
+            ("com.example.*rest", "rest"),
+        ];
+
+        let mut nfa = Router::with_separator('.');
+        let mut tree = TreeRouter::with_separator('.');
+        for (pattern, endpoint) in routes {
+            nfa.try_add(pattern, endpoint).unwrap();
+            tree.try_add(pattern, endpoint).unwrap();
+        }
+
+        for (path, expected) in [
+            (
+                "com.example.service.get",
+                Some((&"method", one_params("method", "get"))),
+            ),
+            ("com.example.health", Some((&"health", empty_params()))),
+            (
+                "com.example.a.b/c",
+                Some((&"rest", one_params("rest", "a.b/c"))),
+            ),
+            (".com.example.health", Some((&"health", empty_params()))),
+            ("com/example/health", None),
+        ] {
+            assert_routed_alike(&nfa, &tree, path, expected);
+        }
+        assert_eq!(
+            nfa.route_segments(&["com", "example", "a", "b"]).unwrap(),
+            (&"rest", one_params("rest", "a.b"))
+        );
+        let (_, params) = nfa.route("com.example.a.b.c").unwrap();
+        assert_eq!(
+            params.find_segments_by("rest", '.'),
+            Some(vec!["a", "b", "c"])
+        );
+        assert_eq!(params.find_segments("rest"), Some(vec!["a.b.c"]));
+
+        let (_, params) = nfa.route_interned("com.example.service.get").unwrap();
+        assert_eq!(params.find("method"), Some("get"));
+
+        let mut dotted = Router::with_separator('.');
+        dotted.add("com.:org.svc", "svc");
+        dotted.add_prefix_middleware("com.", vec![1]);
+        dotted.set_boundary("com.:org.");
+        let (_, params) = dotted.route_interned("com.example.svc").unwrap();
+        assert_eq!(params.find("org"), Some("example"));
+        assert_eq!(
+            dotted.route_with_middleware("com.example.svc").unwrap().2,
+            vec![1]
+        );
+        assert_eq!(
+            dotted.split_mount("com.example.svc"),
+            Some(("com.example", ".svc"))
+        );
+
+        let mut routes: Vec<_> = tree.routes().map(|(p, _)| p).collect();
+        routes.sort();
+        assert_eq!(
+            routes,
+            vec![
+                ".com.example.*rest",
+                ".com.example.health",
+                ".com.example.service.:method"
+            ]
+        );
+
+        assert!(matches!(
+            nfa.try_add("com.*rest.more", "more"),
+            Err(AddError::InvalidPattern(PatternError::WildcardNotLast {
+                index: 1,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "separator `:` is reserved for patterns")]
+    fn reserved_separator() {
+        Router::<()>::with_separator(':');
+    }
+
+    #[test]
+    fn route_exact() {
+        let mut router = Router::new();
+        router.add("/en/hello", "hello");
+        router.add("/en/bye", "bye");
+        router.add("/", "root");
+
+        assert_eq!(router.route_exact("/en/hello"), Some(&"hello"));
+        assert_eq!(router.route_exact("en/bye"), Some(&"bye"));
+        assert_eq!(router.route_exact("/"), Some(&"root"));
+        assert_eq!(router.route_exact("/en"), None);
+        assert_eq!(router.route_exact("/en/hello/"), None);
+
+        router.add("/en/:word", "word");
+        router.add("/fr/*rest", "fr");
+        assert_eq!(router.route_exact("/en/hello"), Some(&"hello"));
+        assert_eq!(router.route_exact("/en/thanks"), None);
+        assert_eq!(router.route_exact("/fr/bonjour"), None);
+        assert_eq!(*router.route("/en/thanks").unwrap().0, "word");
+    }
+
+    #[test]
+    fn unnamed_positional_params() {
+        let routes = [("/foo/:/bar/:name", "foo"), ("/files/:/*", "files")];
+
+        let mut nfa = Router::new();
+        let mut tree = TreeRouter::new();
+        for (pattern, endpoint) in routes {
+            nfa.add(pattern, endpoint);
+            tree.add(pattern, endpoint);
+        }
+
+        for router_params in [
+            nfa.route("/foo/test/bar/x").unwrap().1,
+            tree.route("/foo/test/bar/x").unwrap().1,
+        ] {
+            assert_eq!(router_params.positional(), ["test"]);
+            assert_eq!(router_params.find("name"), Some("x"));
+            assert_eq!(router_params.iter().count(), 1);
+        }
+
+        for router_params in [
+            nfa.route("/files/a/b/c").unwrap().1,
+            tree.route("/files/a/b/c").unwrap().1,
+        ] {
+            assert_eq!(router_params.positional(), ["a", "b/c"]);
+            assert_eq!(router_params.iter().count(), 0);
+        }
+
+        assert!(nfa
+            .route_ref("/foo/test/bar/x")
+            .unwrap()
+            .1
+            .find("")
+            .is_none());
+        assert!(tree
+            .route_ref("/foo/test/bar/x")
+            .unwrap()
+            .1
+            .find("")
+            .is_none());
+    }
+
+    #[test]
+    fn route_into() {
+        let mut router = Router::new();
+        router.add("/posts/:id", "post");
+        router.add("/files/:/*path", "file");
+        router.add("/about", "about");
+        router.trailing_slash(TrailingSlash::Ignore);
+
+        let mut params = Params::new();
+        assert_eq!(router.route_into("/posts/1", &mut params), Some(&"post"));
+        assert_eq!(params, one_params("id", "1"));
+
+        assert_eq!(
+            router.route_into("/files/a/b/c", &mut params),
+            Some(&"file")
+        );
+        assert_eq!(params.find("id"), None);
+        assert_eq!(params.find("path"), Some("b/c"));
+        assert_eq!(params.positional(), ["a"]);
+
+        assert_eq!(router.route_into("/posts/2/", &mut params), Some(&"post"));
+        assert_eq!(params, one_params("id", "2"));
+
+        // cleared on a miss too
+        assert_eq!(router.route_into("/users/1", &mut params), None);
+        assert_eq!(params, empty_params());
+
+        params.insert("stale", "1");
+        assert_eq!(router.route_into("/about", &mut params), Some(&"about"));
+        assert_eq!(params, empty_params());
+    }
+
+    #[test]
+    fn route_by_value() {
+        let mut router: Router<u32> = Router::new();
+        router.add("/posts/:id", 1);
+        router.add("/about", 2);
+
+        assert_eq!(
+            router.route_copied("/posts/7"),
+            Some((1, one_params("id", "7")))
+        );
+        assert_eq!(router.route_copied("/about"), Some((2, empty_params())));
+        assert_eq!(router.route_copied("/users"), None);
+
+        let mut router = Router::new();
+        router.add("/posts/:id", "post".to_string());
+
+        let (endpoint, params) = router.route_cloned("/posts/7").unwrap();
+        assert_eq!(endpoint, "post");
+        assert_eq!(params.find("id"), Some("7"));
+        assert!(router.route_cloned("/users").is_none());
+    }
+
+    #[test]
+    fn bounded_wildcard() {
+        let mut router = TreeRouter::new();
+        router.try_add("/files/*path/download", "download").unwrap();
+        router.try_add("/files/*path/:rev/diff", "diff").unwrap();
+
+        assert_eq!(
+            router.route("/files/a/b/c/download").unwrap(),
+            (&"download", one_params("path", "a/b/c"))
+        );
+        assert_eq!(
+            router.route("/files/a/download").unwrap(),
+            (&"download", one_params("path", "a"))
+        );
+        // the wildcard takes as much as it can
+        assert_eq!(
+            router.route("/files/download/download").unwrap(),
+            (&"download", one_params("path", "download"))
+        );
+        assert_eq!(
+            router.route("/files/a/b/3/diff").unwrap(),
+            (&"diff", two_params("path", "a/b", "rev", "3"))
+        );
+        assert!(router.route("/files/a/b").is_none());
+        assert!(router.route("/files/download").is_none());
+        assert_frozen_agrees(
+            &router,
+            &[
+                "/files/a/b/c/download",
+                "/files/download/download",
+                "/files/a/b/3/diff",
+                "/files/a/b",
+            ],
+        );
+        assert!(router.routes().any(|(p, _)| p == "/files/*path/:rev/diff"));
+
+        router.try_add("/files/*path", "files").unwrap();
+        assert_eq!(
+            router.route("/files/a/b").unwrap(),
+            (&"files", one_params("path", "a/b"))
+        );
+        assert_eq!(
+            router.route("/files/a/b/download").unwrap(),
+            (&"download", one_params("path", "a/b"))
+        );
+
+        assert!(matches!(
+            router.try_add("/a/*x/b/*y", "xy"),
+            Err(AddError::InvalidPattern(PatternError::WildcardNotLast {
+                index: 1,
+                ..
+            }))
+        ));
+        assert!(Router::new()
+            .try_add("/files/*path/download", "download")
+            .is_err());
+    }
+
+    #[test]
+    fn frozen_router() {
+        let mut api = TreeRouter::new();
+        api.add("/users/:id", "user");
+        api.add("/users/:id/posts/:post", "post");
+        api.add("/files/:/*path", "file");
+
+        let mut router = TreeRouter::new();
+        router.add("/", "root");
+        router.add("/about", "about");
+        router.add("/:lang/docs/*page", "docs");
+        router.add("/:lang/blog/:slug", "blog");
+        router.merge("/api/:version", api);
+
+        let frozen = router.clone().freeze();
+        let (endpoint, params) = frozen.route("/api/v1/users/7/posts/3").unwrap();
+        assert_eq!(*endpoint, "post");
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            vec![("id", "7"), ("post", "3"), ("version", "v1")]
+        );
+        let (_, params) = frozen.route("/api/v1/files/a/b/c").unwrap();
+        assert_eq!(params.find("path"), Some("b/c"));
+        assert_eq!(params.positional(), ["a"]);
+        assert!(frozen.route("/api/v1").is_none());
+
+        assert_frozen_agrees(
+            &router,
+            &[
+                "/",
+                "/about",
+                "/about/",
+                "/en/docs/intro/start",
+                "/en/blog/hello",
+                "/en/blog",
+                "/api/v1/users/7",
+                "/api/v1/users/7/posts",
+                "/api/v1/files/a/b/c",
+                "/missing/path",
+            ],
+        );
+    }
+
+    #[test]
+    fn shared_router() {
+        // endpoints need not be `Clone`
+        #[derive(Debug, PartialEq)]
+        struct Handler(&'static str);
+
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let mut router = Router::new();
+        router.add("/posts/:id", Handler("post"));
+        router.add("/about", Handler("about"));
+
+        let shared = router.into_shared();
+        assert_send_sync(&shared);
+
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let path = format!("/posts/{i}");
+                    let (endpoint, params) = shared.route(&path).unwrap();
+                    assert_eq!(*endpoint, Handler("post"));
+                    params.find("id").unwrap().to_string()
+                })
+            })
+            .collect();
+        let ids: Vec<String> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(ids, ["0", "1", "2", "3"]);
+
+        let (endpoint, params) = shared.route_ref("/about").unwrap();
+        assert_eq!(*endpoint, Handler("about"));
+        assert!(params.is_empty());
+        assert!(shared.route("/missing").is_none());
+    }
+
+    #[test]
+    fn collect_owned_patterns() {
+        let config = vec![
+            ("/posts/:id".to_string(), 1),
+            ("/files/*path".to_string(), 2),
+        ];
+
+        let router: Router<_> = config.iter().map(|(p, v)| (p, *v)).collect();
+        assert_eq!(
+            router.route_copied("/posts/7"),
+            Some((1, one_params("id", "7")))
+        );
+
+        // round-trip through the routes of another router
+        let tree: TreeRouter<_> = config.into_iter().collect();
+        let copy: TreeRouter<_> = tree.routes().map(|(p, v)| (p, *v)).collect();
+        assert_eq!(*copy.route("/files/a/b").unwrap().0, 2);
+        assert_eq!(tree.len(), copy.len());
+
+        let mut router = Router::new();
+        router.extend([(std::borrow::Cow::Borrowed("/about"), 3)]);
+        assert_eq!(router.route_copied("/about"), Some((3, empty_params())));
+    }
+
+    #[test]
+    fn tree_children_of() {
+        let router: TreeRouter<_> = [
+            ("/posts", 1),
+            ("/posts/new", 2),
+            ("/posts/:id", 3),
+            ("/posts/:id/edit", 4),
+            ("/posts/:id/comments", 5),
+            ("/posts/archive/*path", 6),
+            ("/users/:id", 7),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(router.children_of("/"), ["posts", "users"]);
+        assert_eq!(router.children_of(""), ["posts", "users"]);
+        assert_eq!(router.children_of("/posts"), [":", "archive", "new"]);
+        assert_eq!(router.children_of("/posts/"), [":", "archive", "new"]);
+        assert_eq!(router.children_of("/posts/:id"), ["comments", "edit"]);
+        // param names do not matter when walking the prefix
+        assert_eq!(router.children_of("/posts/:post"), ["comments", "edit"]);
+        assert_eq!(router.children_of("/posts/archive"), ["*"]);
+        assert!(router.children_of("/posts/new").is_empty());
+        assert!(router.children_of("/missing").is_empty());
+        assert!(router.children_of("/posts/42").is_empty());
+    }
+
+    #[test]
+    fn tree_fallback() {
+        let mut router = TreeRouter::new();
+        router.add("/posts/:id", "post");
+        router.add("/files/*path", "file");
+
+        assert!(router.route_or_fallback("/").is_none());
+        assert!(router.route_or_fallback("/users").is_none());
+
+        assert_eq!(router.set_fallback("not found"), None);
+        for path in ["/", "", "/users", "/posts", "/posts/1/edit"] {
+            assert!(router.route(path).is_none());
+            assert_eq!(
+                router.route_or_fallback(path).unwrap(),
+                (&"not found", empty_params())
+            );
+        }
+        // real routes win over the fallback
+        assert_eq!(
+            router.route_or_fallback("/posts/1").unwrap(),
+            (&"post", one_params("id", "1"))
+        );
+        assert_eq!(
+            router.route_or_fallback("/files/a/b").unwrap(),
+            (&"file", one_params("path", "a/b"))
+        );
+
+        assert_eq!(router.set_fallback("gone"), Some("not found"));
+        router.clear();
+        assert_eq!(*router.route_or_fallback("/posts/1").unwrap().0, "gone");
+
+        router.add("/", "root");
+        let frozen = router.freeze();
+        assert_eq!(*frozen.route_or_fallback("/").unwrap().0, "root");
+        assert_eq!(*frozen.route_or_fallback("/x").unwrap().0, "gone");
+    }
+
+    #[test]
+    fn tree_specificity() {
+        let routes = [
+            ("/posts/new", "new"),
+            ("/posts/:id", "id"),
+            ("/posts/100/comments/10", "100-10"),
+            ("/posts/:post/comments/100", "post-100"),
+            ("/posts/:post/comments/*rest", "rest"),
+            ("/files/:name/raw", "raw"),
+            ("/files/*path", "path"),
+        ];
+        let nfa: Router<_> = routes.into_iter().collect();
+        let tree: TreeRouter<_> = routes.into_iter().collect();
+        // insertion order does not matter either
+        let reversed: Router<_> = routes.into_iter().rev().collect();
+        let reversed_tree: TreeRouter<_> = routes.into_iter().rev().collect();
+
+        for (path, expected) in [
+            ("/posts/new", Some((&"new", empty_params()))),
+            ("/posts/1", Some((&"id", one_params("id", "1")))),
+            ("/posts/100/comments/10", Some((&"100-10", empty_params()))),
+            (
+                "/posts/100/comments/100",
+                Some((&"post-100", one_params("post", "100"))),
+            ),
+            (
+                "/posts/100/comments/7",
+                Some((&"rest", two_params("post", "100", "rest", "7"))),
+            ),
+            (
+                "/posts/100/comments/7/8",
+                Some((&"rest", two_params("post", "100", "rest", "7/8"))),
+            ),
+            ("/files/a/raw", Some((&"raw", one_params("name", "a")))),
+            ("/files/a/b", Some((&"path", one_params("path", "a/b")))),
+            ("/files/a", Some((&"path", one_params("path", "a")))),
+        ] {
+            assert_routed_alike(&nfa, &tree, path, expected.clone());
+            assert_routed_alike(&reversed, &reversed_tree, path, expected);
+        }
+
+        // a prefix of a route is still told apart from an unknown path
+        assert_eq!(
+            tree.try_route("/posts/100/comments").unwrap_err(),
+            RouteError::NoEndpoint
+        );
+        assert_eq!(tree.try_route("/users").unwrap_err(), RouteError::NotFound);
+    }
+
+    #[test]
+    fn owned_patterns() {
+        use std::borrow::Cow;
+
+        let owned = String::from("/posts/:id");
+        let mut router = Router::new();
+        router.add(owned.clone(), "post");
+        router.add(&owned, "post again");
+        router.add(Cow::Borrowed("/users/:id"), "user");
+        router.try_add(format!("/{}", "health"), "health").unwrap();
+        assert_eq!(router.get(&owned), Some(&"post again"));
+        assert!(router.contains(String::from("/users/:name")));
+        *router.at_or_default(format!("/{}", "count")) = "count";
+        assert_eq!(*router.route("/count").unwrap().0, "count");
+
+        let mut other = Router::new();
+        other.add(String::from("/v"), "v");
+        router.merge(String::from("/api"), other);
+        assert_eq!(*router.route("/api/v").unwrap().0, "v");
+        assert_eq!(router.remove(owned), Some("post again"));
+        router.add_with_middleware(String::from("/mw"), "mw", vec![1]);
+        assert_eq!(
+            router.route_with_middleware("/mw").unwrap(),
+            (&"mw", empty_params(), vec![1])
+        );
+
+        let owned = String::from("/posts/:id");
+        let mut router = TreeRouter::new();
+        router.add(owned.clone(), "post");
+        router.add(Cow::Borrowed("/users/:id"), "user");
+        router.try_add(format!("/{}", "health"), "health").unwrap();
+        assert!(router.contains(&owned));
+        *router.at_or_default(String::from("/count")) = "count";
+        assert_eq!(*router.route("/count").unwrap().0, "count");
+
+        let mut other = TreeRouter::new();
+        other.add(String::from("/v"), "v");
+        router.merge(String::from("/api"), other);
+        assert_eq!(*router.route("/api/v").unwrap().0, "v");
+        assert_eq!(router.remove(owned), Some("post"));
+        assert_eq!(router.children_of(String::from("/api")), ["v"]);
+    }
+
+    #[test]
+    fn router_with_capacity() {
+        let patterns: Vec<String> = (0..100).map(|i| format!("/items/{i}/:id")).collect();
+
+        let mut nfa = Router::with_capacity(256);
+        let mut tree = TreeRouter::with_capacity(256);
+        for (i, pattern) in patterns.iter().enumerate() {
+            nfa.add(pattern, i);
+            tree.add(pattern, i);
+        }
+
+        assert_eq!(nfa.len(), 100);
+        assert_eq!(tree.len(), 100);
+        assert_routed_alike(
+            &nfa,
+            &tree,
+            "/items/42/7",
+            Some((&42, one_params("id", "7"))),
+        );
+        assert_routed_alike(&nfa, &tree, "/items/100/7", None);
+    }
+
+    #[test]
+    fn merge_under_param_prefix() {
+        let routes = [
+            ("/users/:id", "user"),
+            ("/users/:id/posts/:post", "post"),
+            ("/", "home"),
+            ("/files/*path", "files"),
+        ];
+
+        let mut nfa = Router::new();
+        // an edge named differently already lies at the prefix
+        nfa.add("/tenants/:org/about", "about");
+        nfa.merge("/tenants/:tenant", routes.into_iter().collect());
+        let mut tree = TreeRouter::new();
+        tree.add("/tenants/:org/about", "about");
+        tree.merge("/tenants/:tenant", routes.into_iter().collect());
+
+        for (path, endpoint, params) in [
+            (
+                "/tenants/acme/users/7",
+                "user",
+                two_params("tenant", "acme", "id", "7"),
+            ),
+            ("/tenants/acme/", "home", one_params("tenant", "acme")),
+            (
+                "/tenants/acme/files/a/b",
+                "files",
+                two_params("tenant", "acme", "path", "a/b"),
+            ),
+            ("/tenants/acme/about", "about", one_params("org", "acme")),
+        ] {
+            assert_routed_alike(&nfa, &tree, path, Some((&endpoint, params)));
+        }
 
-        let endpoint = router.route("/e/f/g").unwrap().0;
-        assert_eq!(*endpoint, "eeffgg");
+        let mut params = two_params("tenant", "acme", "id", "7");
+        params.insert("post", "9");
+        assert_routed_alike(
+            &nfa,
+            &tree,
+            "/tenants/acme/users/7/posts/9",
+            Some((&"post", params)),
+        );
+        assert_eq!(
+            nfa.route_match("/tenants/acme/users/7").unwrap().pattern,
+            "/tenants/:tenant/users/:id"
+        );
+        assert_eq!(
+            tree.route_match("/tenants/acme/users/7").unwrap().pattern,
+            "/tenants/:tenant/users/:id"
+        );
+        assert_routed_alike(&nfa, &tree, "/tenants/acme/users", None);
     }
 
     #[test]
-    fn modify_router2() {
-        let mut router: Router<Vec<&str>> = Router::new();
+    fn case_insensitive_router() {
+        let mut router = Router::new();
 
-        router.at_or_default("/a/b/c").push("abc");
-        router.at_or_default("/a/b/c").push("aabbcc");
+        router.add("/api/v1/profile", "profile");
+        router.add("/api/v1/users/:name", "user");
 
-        let endpoint = router.route("/a/b/c").unwrap().0;
-        assert_eq!(*endpoint, vec!["abc", "aabbcc"]);
+        assert!(router.route("/API/V1/Profile").is_none());
 
-        router.at_or_default("/a/b/c").clear();
+        router.case_insensitive(true);
 
-        let endpoint = router.route("/a/b/c").unwrap().0;
-        assert_eq!(*endpoint, Vec::<&str>::new());
+        assert_eq!(*router.route("/API/V1/Profile").unwrap().0, "profile");
+        assert_eq!(*router.route("/api/v1/profile").unwrap().0, "profile");
+
+        let (endpoint, params) = router.route("/Api/V1/USERS/JohnDoe").unwrap();
+        assert_eq!(*endpoint, "user");
+        assert_eq!(params, one_params("name", "JohnDoe"));
+
+        let mut router = TreeRouter::new();
+
+        router.add("/api/v1/profile", "profile");
+        router.add("/api/v1/users/:name", "user");
+        router.case_insensitive(true);
+
+        assert_eq!(*router.route("/API/V1/Profile").unwrap().0, "profile");
+
+        let (endpoint, params) = router.route("/Api/V1/USERS/JohnDoe").unwrap();
+        assert_eq!(*endpoint, "user");
+        assert_eq!(params, one_params("name", "JohnDoe"));
     }
 
     #[test]
-    fn subtree() {
+    fn case_insensitive_tree_router() {
+        let mut router = TreeRouter::new();
+
+        router.case_insensitive(true);
+        router.add("/Api/users/:name", "user");
+        router.add("/api/Health", "health");
+        router.add("/API/Health", "HEALTH");
+
+        let (endpoint, params) = router.route("/API/USERS/JohnDoe").unwrap();
+        assert_eq!(*endpoint, "user");
+        assert_eq!(params, one_params("name", "JohnDoe"));
+
+        // patterns only differing in case share a route
+        assert_eq!(*router.route("/api/health").unwrap().0, "HEALTH");
+        assert!(router.contains("/API/HEALTH"));
+
+        let routes: Vec<_> = router.routes().map(|(p, _)| p).collect();
+        assert!(routes.contains(&"/api/users/:name".to_string()));
+        assert_frozen_agrees(
+            &router,
+            &["/API/USERS/JohnDoe", "/api/health", "/Api/Health"],
+        );
+
+        router.case_insensitive(false);
+        assert!(router.route("/API/USERS/JohnDoe").is_none());
+    }
+
+    #[test]
+    fn lazy_endpoint() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let inits = Arc::new(AtomicUsize::new(0));
         let mut router = Router::new();
 
-        router.add("/v1/posts", "posts1");
+        let counter = inits.clone();
+        router.add_lazy("/posts/:id", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            String::from("post")
+        });
+        router.add("/users", String::from("users"));
+
+        assert_eq!(inits.load(Ordering::SeqCst), 0);
+        assert_eq!(router.route("/users").unwrap().0, "users");
+        assert_eq!(inits.load(Ordering::SeqCst), 0);
+
+        assert_eq!(router.route("/posts/1").unwrap().0, "post");
+        assert_eq!(router.route("/posts/2").unwrap().0, "post");
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+
+        router.at_or_default("/posts/:id").push('s');
+        assert_eq!(router.route("/posts/1").unwrap().0, "posts");
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+
+        // clones share the pending endpoint, and merging does not build it
+        let inits = Arc::new(AtomicUsize::new(0));
+        let mut router = Router::new();
+
+        let counter = inits.clone();
+        router.add_lazy("/posts/:id", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            String::from("post")
+        });
+
+        let mut cloned = router.clone();
+        let mut merged = Router::new();
+        merged.try_merge("/api", router.clone()).unwrap();
+        assert_eq!(inits.load(Ordering::SeqCst), 0);
+
+        assert_eq!(cloned.route("/posts/1").unwrap().0, "post");
+        assert_eq!(router.route("/posts/1").unwrap().0, "post");
+        assert_eq!(merged.route("/api/posts/1").unwrap().0, "post");
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+
+        cloned.get_mut("/posts/:id").unwrap().push('s');
+        assert_eq!(cloned.route("/posts/1").unwrap().0, "posts");
+        assert_eq!(router.route("/posts/1").unwrap().0, "post");
+    }
+
+    #[test]
+    fn trailing_slash_policy() {
+        let mut router = Router::new();
+
+        router.add("/posts", "posts");
+        router.add("/users/", "users");
+        router.add("/", "root");
+        router.add("/files/*path", "files");
+
+        assert!(router.route("/posts/").is_none());
+
+        router.trailing_slash(TrailingSlash::Ignore);
+
+        assert_eq!(*router.route("/posts").unwrap().0, "posts");
+        assert_eq!(*router.route("/posts/").unwrap().0, "posts");
+        assert_eq!(*router.route("/users").unwrap().0, "users");
+        assert_eq!(*router.route("/").unwrap().0, "root");
+        assert!(router.route("/missing/").is_none());
+
+        let (_, params) = router.route("/files/a/").unwrap();
+        assert_eq!(params, one_params("path", "a/"));
+
+        router.trailing_slash(TrailingSlash::RedirectTo);
+
+        assert_eq!(*router.route("/posts").unwrap().0, "posts");
+        assert_eq!(
+            router.try_route("/posts/").unwrap_err(),
+            RouteError::Redirect("/posts".to_string())
+        );
+        assert_eq!(
+            router.try_route("/users").unwrap_err(),
+            RouteError::Redirect("/users/".to_string())
+        );
+        assert_eq!(
+            router.try_route("/missing/").unwrap_err(),
+            RouteError::NotFound
+        );
+    }
+
+    #[test]
+    fn clear_router() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "post");
+        router.add_prefix_middleware("/posts", vec![1]);
+        router.clear();
+
+        assert!(router.is_empty());
+        assert!(router.route("/posts/1").is_none());
+        assert_eq!(router.param_id("id"), None);
+
+        router.add("/users/:id", "user");
+        assert_eq!(*router.route("/users/1").unwrap().0, "user");
+        assert_eq!(
+            router.route_with_middleware("/users/1").unwrap().2,
+            Vec::<MiddlewareId>::new()
+        );
+        router.clear();
+        assert_eq!(router.stats(), Router::<()>::new().stats());
+
+        let mut router = TreeRouter::new();
+
+        router.add("/posts/:id", "post");
+        router.clear();
+
+        assert!(router.route("/posts/1").is_none());
+        assert_eq!(router.routes().count(), 0);
+
+        router.add("/users/:id", "user");
+        assert_eq!(*router.route("/users/1").unwrap().0, "user");
+        assert!(router.route("/posts/1").is_none());
+        router.clear();
+        assert_eq!(router.stats(), TreeRouter::<()>::new().stats());
+    }
+
+    #[test]
+    fn ambiguous_route_pairs() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "id");
+        router.add("/posts/new", "new");
+        router.add("/posts/:id/comments", "comments");
+        router.add("/users/new", "new-user");
+        router.add("/files/*path", "files");
+        router.add("/files/:name/raw", "raw");
+
+        assert!(!router.is_ambiguous("/posts/1"));
+        assert!(!router.is_ambiguous("/files/a/raw"));
+
+        assert_eq!(
+            router.ambiguous_pairs(),
+            vec![
+                ("/posts/:id".to_string(), "/posts/new".to_string()),
+                ("/files/*path".to_string(), "/files/:name/raw".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn scoped_separator() {
+        let mut router = Router::new();
+
+        router.add("/files/:name", "file");
+        router.scope_separator("/files/archive", '.', |scope| {
+            scope.add("report.:ext", "report");
+            scope.add("*name", "any");
+        });
+
+        let (endpoint, params) = router.route("/files/archive/report.pdf").unwrap();
+        assert_eq!(*endpoint, "report");
+        assert_eq!(params, one_params("ext", "pdf"));
+
+        let (endpoint, params) = router.route("/files/archive/a.tar.gz").unwrap();
+        assert_eq!(*endpoint, "any");
+        assert_eq!(params, one_params("name", "a.tar.gz"));
+
+        let (endpoint, params) = router.route("/files/index.html").unwrap();
+        assert_eq!(*endpoint, "file");
+        assert_eq!(params, one_params("name", "index.html"));
+
+        let mut root = Router::new();
+        root.merge("/v1", router);
+
+        let (endpoint, params) = root.route("/v1/files/archive/report.pdf").unwrap();
+        assert_eq!(*endpoint, "report");
+        assert_eq!(params, one_params("ext", "pdf"));
+
+        // the separator is restored before an escaped `/` is decoded
+        root.set_decode_params(true);
+        let (endpoint, params) = root.route("/v1/files/archive/a%2Fb.c").unwrap();
+        assert_eq!(*endpoint, "any");
+        assert_eq!(params, one_params("name", "a/b.c"));
+        let (endpoint, params) = root.route("/v1/files/archive/report.a%2Eb").unwrap();
+        assert_eq!(*endpoint, "report");
+        assert_eq!(params, one_params("ext", "a.b"));
+    }
+
+    #[test]
+    fn mount_boundary() {
+        let mut router = Router::new();
+
+        router.add("/api/users/:id", "user");
+        router.add("/api", "api");
+        router.add("/health", "health");
+        router.set_boundary("/api");
+
+        assert_eq!(
+            router.split_mount("/api/users/5"),
+            Some(("/api", "/users/5"))
+        );
+        assert_eq!(router.split_mount("/api"), Some(("/api", "")));
+        assert_eq!(router.split_mount("/health"), Some(("", "/health")));
+        assert_eq!(router.split_mount("/missing"), None);
+    }
+
+    #[test]
+    fn combine_routers() {
+        let mut router = Router::new();
+
+        router.add("/posts", vec!["posts"]);
+        router.add("/posts/:id", vec!["post"]);
+
+        let mut other = Router::new();
+
+        other.add("/posts/:id", vec!["post2"]);
+        other.add("/posts/:id/comments", vec!["comments"]);
+        other.add("/users", vec!["users"]);
+
+        router.combine_with(other, |pattern, mut existing, endpoint| {
+            assert_eq!(pattern, "/posts/:id");
+            existing.extend(endpoint);
+            existing
+        });
+
+        assert_eq!(*router.route("/posts").unwrap().0, vec!["posts"]);
+        assert_eq!(*router.route("/posts/1").unwrap().0, vec!["post", "post2"]);
+        assert_eq!(
+            *router.route("/posts/1/comments").unwrap().0,
+            vec!["comments"]
+        );
+        assert_eq!(*router.route("/users").unwrap().0, vec!["users"]);
+    }
+
+    #[test]
+    fn conflicting_routes() {
+        let mut router = Router::new();
+
+        assert!(router.try_add("/posts/:id", "id").is_ok());
+        assert!(router.try_add("/posts/:id/comments", "comments").is_ok());
+        assert!(router.try_add("/files/*path", "files").is_ok());
+
+        let err = router.try_add("/posts/:name", "name").unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/posts/:id");
+
+        let err = router
+            .try_add("/posts/:name/comments", "comments")
+            .unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/posts/:id");
+
+        let err = router.try_add("/posts/:id", "id2").unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/posts/:id");
+        assert_eq!(err.to_string(), "route conflicts with `/posts/:id`");
+
+        let err = router.try_add("/files/*rest", "rest").unwrap_err();
+        assert_eq!(err.conflict().unwrap().existing(), "/files/*path");
+
+        assert_eq!(*router.route("/posts/1").unwrap().0, "id");
+
+        router.add("/posts/:id", "id2");
+        assert_eq!(*router.route("/posts/1").unwrap().0, "id2");
+
+        // add never validates, the pattern try_add refuses is still stored
+        assert!(router.try_add("/a//b", "ab").is_err());
+        router.add("/a//b", "ab");
+        assert!(router.contains("/a//b"));
+    }
+
+    #[test]
+    fn decode_params() {
+        let mut router = Router::new();
+
+        router.add("/users/:name", "user");
+        router.add("/files/*path", "files");
+
+        let (_, params) = router.route("/users/john%20doe").unwrap();
+        assert_eq!(params, one_params("name", "john%20doe"));
+
+        router.set_decode_params(true);
+
+        let (_, params) = router.route("/users/john%20doe").unwrap();
+        assert_eq!(params, one_params("name", "john doe"));
+
+        let (endpoint, params) = router.route("/users/a%2Fb").unwrap();
+        assert_eq!(*endpoint, "user");
+        assert_eq!(params, one_params("name", "a/b"));
+
+        let (_, params) = router.route("/users/abc%2").unwrap();
+        assert_eq!(params, one_params("name", "abc%2"));
+
+        let (_, params) = router.route("/users/%zz%41").unwrap();
+        assert_eq!(params, one_params("name", "%zzA"));
+
+        let (_, params) = router.route("/files/a%20b/c%2Fd").unwrap();
+        assert_eq!(params, one_params("path", "a b/c/d"));
+
+        let mut router = TreeRouter::new();
+
+        router.add("/users/:name", "user");
+        router.set_decode_params(true);
+
+        let (_, params) = router.route("/users/john%20doe").unwrap();
+        assert_eq!(params, one_params("name", "john doe"));
+    }
+
+    #[test]
+    fn decode_static_segments() {
+        let mut router = Router::new();
+
+        router.add("/a b/c", "space");
+        router.add("/a b/:name", "name");
+
+        assert!(router.route("/a%20b/c").is_none());
+
+        router.set_decode_static(true);
+
+        assert_eq!(*router.route("/a%20b/c").unwrap().0, "space");
+        assert_eq!(*router.route("/a b/c").unwrap().0, "space");
+        assert!(router.route("/a%2Fb/c").is_none());
+
+        let (endpoint, params) = router.route("/a%20b/x%20y").unwrap();
+        assert_eq!(*endpoint, "name");
+        assert_eq!(params, one_params("name", "x%20y"));
+
+        let mut router = TreeRouter::new();
+
+        router.add("/a b/c", "space");
+        router.set_decode_static(true);
+
+        assert_eq!(*router.route("/a%20b/c").unwrap().0, "space");
+        assert_frozen_agrees(&router, &["/a%20b/c", "/a b/c", "/a%2Fb/c"]);
+    }
+
+    #[test]
+    fn borrowed_params() {
+        let mut router = Router::new();
+
+        router.add("/posts/:post_id/comments/:id", "comment");
+        router.add("/files/*path", "files");
+        router.add("/foo/:/bar", "unnamed");
+
+        let path = String::from("/posts/1/comments/2");
+        let (endpoint, params) = router.route_ref(&path).unwrap();
+        assert_eq!(*endpoint, "comment");
+        assert_eq!(params.find("post_id"), Some("1"));
+        assert_eq!(params.find("id"), Some("2"));
+        assert_eq!(params.find("missing"), None);
+        assert_eq!(params.to_params(), router.route(&path).unwrap().1);
+
+        let (_, params) = router.route_ref("/files/a/b").unwrap();
+        assert_eq!(params.iter().collect::<Vec<_>>(), vec![("path", "a/b")]);
+
+        let (_, params) = router.route_ref("/foo/x/bar").unwrap();
+        assert!(params.is_empty());
+
+        let mut router = TreeRouter::new();
+
+        router.add("/posts/:post_id/comments/:id", "comment");
+        router.add("/files/*path", "files");
+
+        let (endpoint, params) = router.route_ref(&path).unwrap();
+        assert_eq!(*endpoint, "comment");
+        assert_eq!(params.len(), 2);
+        assert_eq!(params.to_params(), router.route(&path).unwrap().1);
+
+        let (_, params) = router.route_ref("/files/a/b").unwrap();
+        assert_eq!(params.find("path"), Some("a/b"));
+    }
+
+    #[test]
+    fn template_spans() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id/files/*path", "file");
+
+        assert_eq!(
+            router.route_template_spans("/posts/1/files/a/b"),
+            Some(vec![
+                ("posts".to_string(), false),
+                (":id".to_string(), true),
+                ("files".to_string(), false),
+                ("*path".to_string(), true),
+            ])
+        );
+        assert_eq!(router.route_template_spans("/users"), None);
+    }
+
+    #[test]
+    fn interned_params() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "post");
+        router.add("/users/:id/pages/:page", "page");
+
+        let id = router.param_id("id").unwrap();
+        let page = router.param_id("page").unwrap();
+        assert_ne!(id, page);
+        assert_eq!(router.param_id("missing"), None);
+
+        let (endpoint, params) = router.route_interned("/posts/1").unwrap();
+        assert_eq!(*endpoint, "post");
+        assert_eq!(params.find("id"), Some("1"));
+        assert_eq!(params.ids().collect::<Vec<_>>(), vec![id]);
+
+        let (endpoint, params) = router.route_interned("/users/2/pages/3").unwrap();
+        assert_eq!(*endpoint, "page");
+        assert_eq!(params.find("id"), Some("2"));
+        assert_eq!(params.find_id(page), Some("3"));
+        assert_eq!(params.find("missing"), None);
+    }
+
+    #[test]
+    fn iterate_routes() {
+        let mut router = Router::new();
+
+        router.add("/posts", "posts");
+        router.add("/posts/:id", "post");
+        router.add("/files/*path", "files");
 
         let mut subtree = Router::new();
+        subtree.add("/:comment_id", "comment");
+        router.merge("/posts/:id/comments", subtree);
 
-        subtree.add("/new", "new-post");
-        subtree.add("/edit", "edit-post");
+        let mut routes: Vec<_> = router.routes().collect();
+        routes.sort();
+        assert_eq!(
+            routes,
+            vec![
+                ("/files/*path".to_string(), &"files"),
+                ("/posts".to_string(), &"posts"),
+                ("/posts/:id".to_string(), &"post"),
+                ("/posts/:id/comments/:comment_id".to_string(), &"comment"),
+            ]
+        );
 
-        router.merge("/v1/posts", subtree.clone());
+        let mut rebuilt = Router::new();
+        for (pattern, endpoint) in router.routes() {
+            rebuilt.add(&pattern, *endpoint);
+        }
+        assert_eq!(
+            rebuilt.route("/posts/1/comments/2"),
+            router.route("/posts/1/comments/2")
+        );
 
-        let endpoint = router.route("/v1/posts").unwrap().0;
+        let mut router = TreeRouter::new();
 
-        assert_eq!(*endpoint, "posts1");
+        router.add("/posts", "posts");
+        router.add("/posts/:id", "post");
+        router.add("/files/*path", "files");
 
-        let endpoint = router.route("/v1/posts/new").unwrap().0;
+        let mut routes: Vec<_> = router.routes().collect();
+        routes.sort();
+        assert_eq!(
+            routes,
+            vec![
+                ("/files/*path".to_string(), &"files"),
+                ("/posts".to_string(), &"posts"),
+                ("/posts/:id".to_string(), &"post"),
+            ]
+        );
+    }
 
-        assert_eq!(*endpoint, "new-post");
+    #[test]
+    fn remove_route() {
+        let mut router = Router::new();
 
-        router.merge("/v2/posts/", subtree);
+        router.add("/posts", "posts");
+        router.add("/posts/:id", "post");
+        router.add("/posts/:id/comments", "comments");
 
-        assert_eq!(*router.route("/v2/posts/new").unwrap().0, "new-post");
-        assert_eq!(*router.route("/v2/posts/edit").unwrap().0, "edit-post");
+        assert_eq!(router.remove("/users"), None);
+        assert_eq!(router.remove("/posts/:id"), Some("post"));
+        assert_eq!(router.remove("/posts/:id"), None);
+
+        assert!(router.route("/posts/1").is_none());
+        assert_eq!(*router.route("/posts").unwrap().0, "posts");
+        assert_eq!(*router.route("/posts/1/comments").unwrap().0, "comments");
+
+        let mut router = TreeRouter::new();
+
+        router.add("/posts", "posts");
+        router.add("/posts/:id", "post");
+        router.add("/posts/:id/comments", "comments");
+
+        assert_eq!(router.remove("/users"), None);
+        assert_eq!(router.remove("/posts/:id"), Some("post"));
+        assert_eq!(router.remove("/posts/:id"), None);
+
+        assert!(router.route("/posts/1").is_none());
+        assert_eq!(*router.route("/posts").unwrap().0, "posts");
+        assert_eq!(*router.route("/posts/1/comments").unwrap().0, "comments");
+    }
+
+    #[test]
+    fn route_context() {
+        #[derive(Debug, PartialEq)]
+        struct Deps {
+            pool: usize,
+            beta: bool,
+        }
+
+        let mut router: Router<&str, Deps> = Router::with_context();
+
+        router.add_with_context(
+            "/posts/:id",
+            "post",
+            Deps {
+                pool: 1,
+                beta: false,
+            },
+        );
+        router.add("/posts", "posts");
+
+        let (endpoint, context, params) = router.route_with_context("/posts/1").unwrap();
+        assert_eq!(*endpoint, "post");
+        assert_eq!(
+            context,
+            Some(&Deps {
+                pool: 1,
+                beta: false
+            })
+        );
+        assert_eq!(params, one_params("id", "1"));
+
+        let (endpoint, context, _) = router.route_with_context("/posts").unwrap();
+        assert_eq!(*endpoint, "posts");
+        assert_eq!(context, None);
+
+        // replacing the endpoint drops its context, removing the route drops both
+        router.add_with_context(
+            "/ctx",
+            "ctx",
+            Deps {
+                pool: 3,
+                beta: false,
+            },
+        );
+        router.add("/ctx", "plain");
+        assert_eq!(router.route_with_context("/ctx").unwrap().1, None);
+        router.add_with_context(
+            "/ctx",
+            "ctx",
+            Deps {
+                pool: 3,
+                beta: false,
+            },
+        );
+        router.add_lazy("/ctx", || "lazy");
+        assert_eq!(
+            router.route_with_context("/ctx").unwrap(),
+            (&"lazy", None, empty_params())
+        );
+        router.add_with_context(
+            "/ctx",
+            "ctx",
+            Deps {
+                pool: 3,
+                beta: false,
+            },
+        );
+        assert_eq!(router.remove("/ctx"), Some("ctx"));
+        router.add("/ctx", "plain");
+        assert_eq!(router.route_with_context("/ctx").unwrap().1, None);
+        router.add_with_middleware("/ctx", "mw", vec![1]);
+        router.remove("/ctx");
+        router.add("/ctx", "plain");
+        assert!(router.route_with_middleware("/ctx").unwrap().2.is_empty());
+
+        let mut subtree = Router::with_context();
+        subtree.add_with_context(
+            "/new",
+            "new-post",
+            Deps {
+                pool: 2,
+                beta: true,
+            },
+        );
+        router.merge("/v2/posts", subtree);
+
+        let (_, context, _) = router.route_with_context("/v2/posts/new").unwrap();
+        assert_eq!(
+            context,
+            Some(&Deps {
+                pool: 2,
+                beta: true
+            })
+        );
+    }
+
+    #[test]
+    fn static_only_router() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "id");
+        router.add("/posts/new", "new");
+        router.add("/files/*path", "files");
+
+        let (endpoint, params) = router.route_static_only("/posts/new").unwrap();
+        assert_eq!(*endpoint, "new");
+        assert_eq!(params, empty_params());
+
+        assert!(router.route_static_only("/posts/1").is_none());
+        assert!(router.route_static_only("/posts").is_none());
+        assert!(router.route_static_only("/files/a/b").is_none());
+        assert_eq!(*router.route("/posts/1").unwrap().0, "id");
+    }
+
+    #[test]
+    fn empty_router() {
+        let router: Router<&str> = Router::new();
+
+        assert!(router.is_empty());
+        assert!(router.route("/").is_none());
+        assert!(router.route("").is_none());
+        assert_eq!(
+            router.try_route("/posts/1").unwrap_err(),
+            RouteError::NotFound
+        );
+
+        let mut router = Router::new();
+        router.add("/posts", "posts");
+
+        assert!(!router.is_empty());
+        assert!(router.route("/posts").is_some());
+    }
+
+    #[test]
+    fn route_middleware() {
+        let mut router = Router::new();
+
+        router.add_prefix_middleware("/", vec![1]);
+        router.add_prefix_middleware("/api/", vec![2, 3]);
+        router.add_with_middleware("/api/posts/:id", "post", vec![4]);
+        router.add("/api/posts", "posts");
+
+        let (endpoint, params, middlewares) = router.route_with_middleware("/api/posts/1").unwrap();
+        assert_eq!(*endpoint, "post");
+        assert_eq!(params, one_params("id", "1"));
+        assert_eq!(middlewares, vec![1, 2, 3, 4]);
+
+        let (_, _, middlewares) = router.route_with_middleware("/api/posts").unwrap();
+        assert_eq!(middlewares, vec![1, 2, 3]);
+
+        let mut subtree = Router::new();
+        subtree.add_prefix_middleware("/", vec![5]);
+        subtree.add_with_middleware("/new", "new-comment", vec![6]);
+
+        router.merge("/api/posts/:id/comments", subtree);
+
+        let (endpoint, _, middlewares) = router
+            .route_with_middleware("/api/posts/1/comments/new")
+            .unwrap();
+        assert_eq!(*endpoint, "new-comment");
+        assert_eq!(middlewares, vec![1, 2, 3, 5, 6]);
+    }
+
+    #[test]
+    fn params_find_as() {
+        #[derive(Debug, PartialEq)]
+        struct Slug(String);
+
+        impl FromStr for Slug {
+            type Err = ();
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                if s.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+                    Ok(Slug(s.to_string()))
+                } else {
+                    Err(())
+                }
+            }
+        }
+
+        let mut params = Params::new();
+        params.insert("id", "42");
+        params.insert("slug", "hello-world");
+        params.insert("bad", "4x2");
+
+        assert_eq!(params.find_as::<u64>("id"), Some(Ok(42)));
+        assert_eq!(params.find_as::<u64>("missing"), None);
+        assert!(matches!(params.find_as::<u64>("bad"), Some(Err(_))));
+
+        assert_eq!(
+            params.find_as::<Slug>("slug"),
+            Some(Ok(Slug("hello-world".to_string())))
+        );
+        assert_eq!(params.find_as::<Slug>("bad"), Some(Err(())));
+
+        assert_eq!(params.get_or("id", 0u64), 42);
+        assert_eq!(params.get_or("missing", 7u64), 7);
+        assert_eq!(params.get_or("bad", 7u64), 7);
+    }
+
+    #[test]
+    fn params_contains_key() {
+        let params = one_params("id", "1");
+
+        let key = String::from("id");
+        assert!(params.contains_key("id"));
+        assert!(params.contains_key(&key));
+        assert!(params.contains_key(key));
+        assert!(!params.contains_key("name"));
     }
 
     fn empty_params() -> Params {
         Params::new()
     }
 
+    /// Route every path with the router and its frozen copy, they must agree.
+    /// Build both routers from the routes, see [`assert_routed_alike`].
+    #[track_caller]
+    fn assert_routers_agree<T>(routes: &[(&str, T)], path: &str, expected: Option<(&T, Params)>)
+    where
+        T: Clone + PartialEq + fmt::Debug,
+    {
+        let nfa: Router<T> = routes.iter().cloned().collect();
+        let tree: TreeRouter<T> = routes.iter().cloned().collect();
+
+        assert_routed_alike(&nfa, &tree, path, expected);
+    }
+
+    /// The NFA, the tree and the frozen tree all route the path to `expected`.
+    #[track_caller]
+    fn assert_routed_alike<T>(
+        nfa: &Router<T>,
+        tree: &TreeRouter<T>,
+        path: &str,
+        expected: Option<(&T, Params)>,
+    ) where
+        T: Clone + PartialEq + fmt::Debug,
+    {
+        assert_eq!(nfa.route(path), expected, "routing `{path}` by the NFA");
+        assert_eq!(tree.route(path), expected, "routing `{path}` by the tree");
+        assert_frozen_agrees(tree, &[path]);
+    }
+
+    fn assert_frozen_agrees<T>(router: &TreeRouter<T>, paths: &[&str])
+    where
+        T: Clone + PartialEq + fmt::Debug,
+    {
+        let frozen = router.clone().freeze();
+
+        for path in paths {
+            assert_eq!(router.route(path), frozen.route(path), "routing `{path}`");
+        }
+    }
+
+    fn positional_params(values: &[&str]) -> Params {
+        let mut params = Params::new();
+        params.positional = values.iter().map(|v| v.to_string()).collect();
+        params
+    }
+
     fn one_params(key: &str, value: &str) -> Params {
         let mut map = Params::new();
         map.insert(key, value);
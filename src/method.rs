@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use crate::{Params, RouteError, Router};
+
+/// Router dispatching on HTTP method after matching the path.
+///
+/// Methods are compared case-insensitively.
+#[derive(Debug, Clone)]
+pub struct MethodRouter<T> {
+    router: Router<BTreeMap<String, T>>,
+}
+
+impl<T> MethodRouter<T> {
+    pub fn new() -> Self {
+        MethodRouter {
+            router: Router::new(),
+        }
+    }
+
+    /// Add endpoint for the method on the pattern, replacing the previous one.
+    pub fn add(&mut self, method: &str, pattern: impl AsRef<str>, endpoint: T) -> Option<T> {
+        self.router
+            .at_or_default(pattern)
+            .insert(method.to_ascii_uppercase(), endpoint)
+    }
+
+    /// Remove endpoint for the method on the pattern, the route itself is removed
+    /// with its last method, so it no longer shadows other routes.
+    pub fn remove(&mut self, method: &str, pattern: impl AsRef<str>) -> Option<T> {
+        let pattern = pattern.as_ref();
+        let methods = self.router.get_mut(pattern)?;
+        let removed = methods.remove(&method.to_ascii_uppercase());
+
+        if methods.is_empty() {
+            self.router.remove(pattern);
+        }
+
+        removed
+    }
+
+    pub fn route(&self, method: &str, path: &str) -> Option<(&T, Params)> {
+        self.try_route(method, path).ok()
+    }
+
+    /// Route the path, fail with [`RouteError::MethodNotAllowed`] when the path matches
+    /// but no endpoint is registered for the method.
+    pub fn try_route(&self, method: &str, path: &str) -> Result<(&T, Params), RouteError> {
+        let (methods, params) = self.router.try_route(path)?;
+
+        match methods.get(&method.to_ascii_uppercase()) {
+            Some(endpoint) => Ok((endpoint, params)),
+            None if methods.is_empty() => Err(RouteError::NotFound),
+            None => Err(RouteError::MethodNotAllowed(
+                methods.keys().cloned().collect(),
+            )),
+        }
+    }
+
+    /// Methods registered for the route matching the path, empty when nothing matches,
+    /// suitable for the `Allow` header of a 405 response.
+    pub fn allowed_methods(&self, path: &str) -> Vec<String> {
+        match self.router.route(path) {
+            Some((methods, _params)) => methods.keys().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl<T> Default for MethodRouter<T> {
+    fn default() -> Self {
+        MethodRouter::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn method_router() {
+        let mut router = MethodRouter::new();
+
+        router.add("GET", "/posts/:id", "show");
+        router.add("post", "/posts/:id", "update");
+        router.add("GET", "/posts", "index");
+
+        let (endpoint, params) = router.route("get", "/posts/1").unwrap();
+        assert_eq!(*endpoint, "show");
+        assert_eq!(params.find("id"), Some("1"));
+        assert_eq!(*router.route("POST", "/posts/1").unwrap().0, "update");
+
+        assert_eq!(
+            router.try_route("DELETE", "/posts/1").unwrap_err(),
+            RouteError::MethodNotAllowed(vec!["GET".to_string(), "POST".to_string()])
+        );
+        assert_eq!(
+            router.try_route("GET", "/users").unwrap_err(),
+            RouteError::NotFound
+        );
+
+        assert_eq!(router.allowed_methods("/posts/1"), vec!["GET", "POST"]);
+        assert_eq!(router.allowed_methods("/posts"), vec!["GET"]);
+        assert!(router.allowed_methods("/users").is_empty());
+
+        assert_eq!(router.remove("POST", "/posts/:id"), Some("update"));
+        assert_eq!(router.remove("POST", "/users"), None);
+        assert_eq!(
+            router.try_route("POST", "/posts/1").unwrap_err(),
+            RouteError::MethodNotAllowed(vec!["GET".to_string()])
+        );
+        assert_eq!(
+            router
+                .try_route("POST", "/posts/1")
+                .unwrap_err()
+                .to_string(),
+            "route method not allowed, allow `GET`"
+        );
+        assert_eq!(
+            router.try_route("GET", "/users").unwrap_err(),
+            RouteError::NotFound
+        );
+
+        // removing the last method removes the route, so it no longer shadows others
+        let mut router = MethodRouter::new();
+        router.add("GET", "/a/:x", "param");
+        router.add("GET", "/a/*w", "wildcard");
+        assert_eq!(*router.route("GET", "/a/b").unwrap().0, "param");
+        assert_eq!(router.remove("GET", "/a/:x"), Some("param"));
+        assert_eq!(router.remove("GET", "/a/:x"), None);
+        assert_eq!(*router.try_route("GET", "/a/b").unwrap().0, "wildcard");
+        assert!(router.allowed_methods("/a/b").contains(&"GET".to_string()));
+    }
+}
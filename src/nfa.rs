@@ -1,8 +1,30 @@
-use std::collections::BTreeMap;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::OnceLock,
+};
+
+use crate::{
+    constraint::{Constraint, Reserved},
+    percent, BudgetExceeded, MatchKind, RouteError, RouterStats,
+};
 
 const CHAR_PATH_SEP: char = '/';
 const CHAR_PARAM: char = ':';
 const CHAR_WILDCARD: char = '*';
+const CHAR_ESCAPE: char = '\\';
+
+/// Live roads of a search, kept inline for the few roads of typical paths.
+#[cfg(feature = "smallvec")]
+type Roads = smallvec::SmallVec<[Road; 4]>;
+#[cfg(not(feature = "smallvec"))]
+type Roads = Vec<Road>;
+
+/// Transitions taken from a state by a segment, rarely more than a few.
+#[cfg(feature = "smallvec")]
+type Steps<'a> = smallvec::SmallVec<[(Capture<'a>, usize); 2]>;
+#[cfg(not(feature = "smallvec"))]
+type Steps<'a> = Vec<(Capture<'a>, usize)>;
 
 #[derive(Debug, Clone)]
 struct Entry {
@@ -49,6 +71,15 @@ impl Transitions {
             Pattern::Static(p) => {
                 self.static_entries.insert(p, index);
             }
+            // constrained params are tried first, so they win over plain ones on ties
+            p @ Pattern::Param(_, Some(_)) => {
+                let at = self
+                    .dynamic_entries
+                    .iter()
+                    .position(|entry| !matches!(entry.pat, Pattern::Param(_, Some(_))))
+                    .unwrap_or(self.dynamic_entries.len());
+                self.dynamic_entries.insert(at, Entry::new(p, index));
+            }
             p => {
                 self.dynamic_entries.push(Entry::new(p, index));
             }
@@ -67,20 +98,28 @@ impl Transitions {
         ret
     }
 
-    fn capture<'a: 'b, 'b>(&'b self, seg: &'a str, path: &'a str) -> Vec<(Capture, usize)> {
-        let mut captures = Vec::new();
-
-        if let Some(index) = self.static_entries.get(seg) {
-            captures.push((Capture::Static, *index));
+    fn capture<'a: 'b, 'b>(
+        &'b self,
+        key: &str,
+        case_insensitive: bool,
+        seg: &'a str,
+        pos: usize,
+    ) -> Steps<'b> {
+        let mut captures = Steps::new();
+
+        if let Some(index) = self.capture_static(key, case_insensitive) {
+            captures.push((Capture::Static, index));
         }
 
         for entry in &self.dynamic_entries {
             match &entry.pat {
-                Pattern::Param(name) => {
-                    captures.push((Capture::Param(name, seg), entry.index));
+                Pattern::Param(name, constraint) => {
+                    if constraint.as_ref().is_none_or(|c| c.is_match(seg)) {
+                        captures.push((Capture::Param(name, seg), entry.index));
+                    }
                 }
                 Pattern::Wildcard(name) => {
-                    captures.push((Capture::Wildcard(name, path), entry.index));
+                    captures.push((Capture::Wildcard(name, pos), entry.index));
                 }
                 _ => unreachable!(),
             }
@@ -89,21 +128,31 @@ impl Transitions {
         captures
     }
 
-    fn capture_static(&self, seg: &str) -> Option<usize> {
-        self.static_entries.get(seg).copied()
+    fn capture_static(&self, seg: &str, case_insensitive: bool) -> Option<usize> {
+        match self.static_entries.get(seg) {
+            Some(index) => Some(*index),
+            None if case_insensitive => self
+                .static_entries
+                .iter()
+                .find(|(k, _v)| k.eq_ignore_ascii_case(seg))
+                .map(|(_k, v)| *v),
+            None => None,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 struct State {
     index: usize,
+    parent: usize,
     transitions: Transitions,
 }
 
 impl State {
-    fn new(index: usize) -> Self {
+    fn new(index: usize, parent: usize) -> Self {
         State {
             index,
+            parent,
             transitions: Transitions::new(),
         }
     }
@@ -112,7 +161,7 @@ impl State {
 #[derive(Debug, Clone)]
 enum Pattern {
     Static(String),
-    Param(String),
+    Param(String, Option<Constraint>),
     Wildcard(String),
 }
 
@@ -120,18 +169,39 @@ impl PartialEq for Pattern {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Static(l0), Self::Static(r0)) => l0 == r0,
-            (Self::Param(_l0), Self::Param(_r0)) => true,
+            (Self::Param(_l0, l1), Self::Param(_r0, r1)) => l1 == r1,
             (Self::Wildcard(_l0), Self::Wildcard(_r0)) => true,
             _ => false,
         }
     }
 }
 
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Static(p) if p.starts_with([CHAR_PARAM, CHAR_WILDCARD, CHAR_ESCAPE]) => {
+                write!(f, "{CHAR_ESCAPE}{p}")
+            }
+            Pattern::Static(p) => write!(f, "{p}"),
+            Pattern::Param(p, None) => write!(f, "{CHAR_PARAM}{p}"),
+            Pattern::Param(p, Some(c)) => write!(f, "{CHAR_PARAM}{p}{c}"),
+            Pattern::Wildcard(p) => write!(f, "{CHAR_WILDCARD}{p}"),
+        }
+    }
+}
+
 impl Pattern {
     fn from_str(pat: impl AsRef<str>) -> Self {
         let pat = pat.as_ref();
         match pat.chars().next() {
-            Some(CHAR_PARAM) => Pattern::Param(pat[1..].to_owned()),
+            // `\:name` and `\*name` are literal segments
+            Some(CHAR_ESCAPE) if pat[1..].starts_with([CHAR_PARAM, CHAR_WILDCARD, CHAR_ESCAPE]) => {
+                Pattern::Static(pat[1..].to_owned())
+            }
+            Some(CHAR_PARAM) => {
+                let (name, constraint) = Constraint::parse(&pat[1..]);
+                Pattern::Param(name.to_owned(), constraint)
+            }
             Some(CHAR_WILDCARD) => Pattern::Wildcard(pat[1..].to_owned()),
             _ => Pattern::Static(pat.to_owned()),
         }
@@ -142,6 +212,17 @@ impl Pattern {
 pub struct Nfa {
     states: Vec<State>,
     acceptances: Vec<bool>,
+    /// Param names of each route, edges are shared regardless of names.
+    names: BTreeMap<usize, Vec<String>>,
+    /// Segments never captured by params.
+    reserved: Reserved,
+    decode_static: bool,
+    case_insensitive: bool,
+    /// Separator of path segments.
+    sep: char,
+    /// States keyed by their full path while every transition is static,
+    /// built by the first search and reset whenever states change.
+    static_paths: OnceLock<Option<HashMap<String, usize>>>,
 }
 
 impl Nfa {
@@ -149,20 +230,77 @@ impl Nfa {
         let mut this = Nfa {
             states: Vec::new(),
             acceptances: Vec::new(),
+            names: BTreeMap::new(),
+            reserved: Reserved::default(),
+            decode_static: false,
+            case_insensitive: false,
+            sep: CHAR_PATH_SEP,
+            static_paths: OnceLock::new(),
         };
 
-        this.new_state();
+        this.new_state(0);
 
         this
     }
 
-    fn new_state(&mut self) -> usize {
+    /// Reserve room for at least `additional` more states.
+    pub fn reserve(&mut self, additional: usize) {
+        self.states.reserve(additional);
+        self.acceptances.reserve(additional);
+    }
+
+    /// Reset to a single start state, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.states.clear();
+        self.acceptances.clear();
+        self.names.clear();
+
+        self.new_state(0);
+    }
+
+    /// Set the separator of path segments, which applies to patterns and paths alike.
+    pub fn set_separator(&mut self, sep: char) {
+        self.sep = sep;
+        self.static_paths.take();
+    }
+
+    pub fn separator(&self) -> char {
+        self.sep
+    }
+
+    /// Percent-decode segments before matching static transitions.
+    pub fn set_decode_static(&mut self, enable: bool) {
+        self.decode_static = enable;
+        self.static_paths.take();
+    }
+
+    /// Match static transitions ASCII case-insensitively.
+    pub fn set_case_insensitive(&mut self, enable: bool) {
+        self.case_insensitive = enable;
+        self.static_paths.take();
+    }
+
+    /// Segments which params never capture, they only match static transitions.
+    pub fn set_reserved<S: ToString>(&mut self, words: impl IntoIterator<Item = S>) {
+        self.reserved = Reserved::new(words);
+    }
+
+    fn static_key<'s>(&self, seg: &'s str) -> Cow<'s, str> {
+        if self.decode_static {
+            percent::decode(seg)
+        } else {
+            Cow::Borrowed(seg)
+        }
+    }
+
+    fn new_state(&mut self, parent: usize) -> usize {
         let new_index = self.states.len();
 
-        let new_state = State::new(new_index);
+        let new_state = State::new(new_index, parent);
 
         self.states.push(new_state);
         self.acceptances.push(false);
+        self.static_paths.take();
 
         new_index
     }
@@ -183,9 +321,166 @@ impl Nfa {
         self.acceptances[state]
     }
 
+    /// Full paths of all states, `None` once any param or wildcard transition exists,
+    /// or when segments are decoded or folded before matching.
+    fn static_paths(&self) -> Option<&HashMap<String, usize>> {
+        self.static_paths
+            .get_or_init(|| {
+                if self.decode_static || self.case_insensitive {
+                    return None;
+                }
+
+                let mut paths = HashMap::with_capacity(self.states.len());
+                let mut stack = vec![(String::new(), self.start_state())];
+                while let Some((path, state)) = stack.pop() {
+                    let transitions = &self.get_state(state).transitions;
+                    if !transitions.dynamic_entries.is_empty() {
+                        return None;
+                    }
+
+                    let start = state == self.start_state();
+                    for (seg, &next) in &transitions.static_entries {
+                        let mut next_path = path.clone();
+                        if !start {
+                            next_path.push(self.sep);
+                        }
+                        next_path.push_str(seg);
+                        stack.push((next_path, next));
+                    }
+                    // the start state has no segment, the root route owns the empty path
+                    if !start {
+                        paths.insert(path, state);
+                    }
+                }
+
+                Some(paths)
+            })
+            .as_ref()
+    }
+
+    /// Size of the compiled states, states are created after their parent.
+    pub fn stats(&self) -> RouterStats {
+        let mut depths = vec![0; self.states.len()];
+        for state in self.states.iter().skip(1) {
+            depths[state.index] = depths[state.parent] + 1;
+        }
+
+        RouterStats {
+            state_count: self.states.len(),
+            accepting_count: self.acceptances.iter().filter(|a| **a).count(),
+            max_depth: depths.into_iter().max().unwrap_or_default(),
+            param_edge_count: self
+                .states
+                .iter()
+                .flat_map(|state| &state.transitions.dynamic_entries)
+                .filter(|entry| matches!(entry.pat, Pattern::Param(_, _)))
+                .count(),
+        }
+    }
+
+    /// Reconstruct the pattern string leading to the state.
+    pub(crate) fn pattern(&self, state: usize) -> String {
+        let mut pattern = String::new();
+
+        for (seg, _dynamic) in self.segments(state) {
+            pattern.push(self.sep);
+            pattern.push_str(&seg);
+        }
+
+        pattern
+    }
+
+    /// Reconstruct the pattern segments leading to the state,
+    /// each flagged whether it is a param or wildcard.
+    pub(crate) fn segments(&self, state: usize) -> Vec<(String, bool)> {
+        self.patterns(state)
+            .into_iter()
+            .map(|pat| {
+                let dynamic = !matches!(pat, Pattern::Static(_));
+
+                (pat.to_string(), dynamic)
+            })
+            .collect()
+    }
+
+    /// Patterns of the transitions leaving the state, static ones first.
+    pub(crate) fn next_patterns(&self, state: usize) -> Vec<String> {
+        self.get_state(state)
+            .transitions
+            .entries()
+            .into_iter()
+            .map(|entry| entry.pat.to_string())
+            .collect()
+    }
+
+    fn patterns(&self, state: usize) -> Vec<Pattern> {
+        let states = self.ancestors(state);
+
+        let mut patterns: Vec<Pattern> = states
+            .windows(2)
+            .map(|pair| {
+                let (parent, child) = (pair[0], pair[1]);
+
+                self.get_state(parent)
+                    .transitions
+                    .entries()
+                    .into_iter()
+                    .find(|entry| entry.index == child)
+                    .expect("transition not exist")
+                    .pat
+            })
+            .collect();
+
+        if let Some(names) = self.names.get(&state) {
+            let mut names = names.iter().rev();
+            for pat in patterns.iter_mut().rev() {
+                if let Pattern::Param(name, _) | Pattern::Wildcard(name) = pat {
+                    match names.next() {
+                        Some(n) => n.clone_into(name),
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        patterns
+    }
+
+    /// Whether some path would be matched by the patterns of both states.
+    pub(crate) fn overlaps(&self, a: usize, b: usize) -> bool {
+        fn overlaps(a: &[Pattern], b: &[Pattern]) -> bool {
+            match (a.split_first(), b.split_first()) {
+                (None, None) => true,
+                (None, Some(_)) | (Some(_), None) => false,
+                // wildcard consumes all remaining segments
+                (Some((Pattern::Wildcard(_), _)), Some(_))
+                | (Some(_), Some((Pattern::Wildcard(_), _))) => true,
+                (Some((Pattern::Static(l), _)), Some((Pattern::Static(r), _))) if l != r => false,
+                (Some((_, a)), Some((_, b))) => overlaps(a, b),
+            }
+        }
+
+        overlaps(&self.patterns(a), &self.patterns(b))
+    }
+
+    /// Get states from start state to `state`, both inclusive.
+    pub(crate) fn ancestors(&self, state: usize) -> Vec<usize> {
+        let mut states = vec![state];
+        let mut index = state;
+
+        while index != self.start_state() {
+            index = self.get_state(index).parent;
+            states.push(index);
+        }
+
+        states.reverse();
+
+        states
+    }
+
     pub fn locate(&mut self, path: &str) -> usize {
-        let path = path.trim_start_matches(CHAR_PATH_SEP);
-        let segs = path.split(CHAR_PATH_SEP);
+        let path = path.trim_start_matches(self.sep);
+        let segs = path.split(self.sep);
 
         let mut index = self.start_state();
 
@@ -199,7 +494,7 @@ impl Nfa {
                     index = s;
                 }
                 None => {
-                    let new_state = self.new_state();
+                    let new_state = self.new_state(index);
                     self.get_state_mut(index).transitions.push(pat, new_state);
 
                     index = new_state;
@@ -210,33 +505,162 @@ impl Nfa {
         index
     }
 
+    /// Find the state of the path without creating new states.
+    pub fn find_state(&self, path: &str) -> Option<usize> {
+        let path = path.trim_start_matches(self.sep);
+
+        let mut index = self.start_state();
+
+        for seg in path.split(self.sep) {
+            let pat = Pattern::from_str(seg);
+
+            index = self.get_state(index).transitions.get(&pat)?;
+        }
+
+        Some(index)
+    }
+
+    /// Find the state conflicting with the path, which is either a param or wildcard
+    /// transition named differently, or the accepted state of the same path.
+    pub fn find_conflict(&self, path: &str) -> Option<usize> {
+        let path = path.trim_start_matches(self.sep);
+
+        let mut index = self.start_state();
+
+        for seg in path.split(self.sep) {
+            let pat = Pattern::from_str(seg);
+            let transitions = &self.get_state(index).transitions;
+
+            let next = transitions.get(&pat)?;
+
+            for entry in &transitions.dynamic_entries {
+                match (&entry.pat, &pat) {
+                    (Pattern::Param(l, lc), Pattern::Param(r, rc)) if lc == rc && l != r => {
+                        return Some(entry.index);
+                    }
+                    (Pattern::Wildcard(l), Pattern::Wildcard(r)) if l != r => {
+                        return Some(entry.index);
+                    }
+                    _ => {}
+                }
+            }
+
+            index = next;
+        }
+
+        self.get_acceptance(index).then_some(index)
+    }
+
     pub fn accept(&mut self, state: usize) {
         if state != self.start_state() {
             self.acceptances[state] = true;
         }
     }
 
+    /// Clear acceptance of the state, return whether it was accepted.
+    pub fn reject(&mut self, state: usize) -> bool {
+        std::mem::replace(&mut self.acceptances[state], false)
+    }
+
     pub fn insert(&mut self, path: &str) -> usize {
         let state = self.locate(path);
         self.accept(state);
+
+        let names = self.route_names(path);
+        self.names.insert(state, names);
+
         state
     }
 
-    pub fn search<'a: 'b, 'b>(&'a self, path: &'b str) -> Option<Match<'b>> {
-        let mut path = path.trim_start_matches(CHAR_PATH_SEP);
+    /// Names of the params and wildcards of the pattern, in path order.
+    pub fn route_names(&self, path: &str) -> Vec<String> {
+        path.split(self.sep)
+            .filter_map(|seg| seg.strip_prefix([CHAR_PARAM, CHAR_WILDCARD]))
+            .map(|name| Constraint::name(name).to_owned())
+            .collect()
+    }
+
+    pub fn search<'a: 'b, 'b>(&'a self, path: &'b str) -> Result<Match<'b>, RouteError> {
+        match self.search_budgeted(path, usize::MAX) {
+            Ok(found) => found,
+            Err(BudgetExceeded) => unreachable!("search is unbounded"),
+        }
+    }
+
+    /// Search the path, giving up once more than `max_steps` roads have been walked.
+    pub fn search_budgeted<'a: 'b, 'b>(
+        &'a self,
+        path: &'b str,
+        max_steps: usize,
+    ) -> Result<Result<Match<'b>, RouteError>, BudgetExceeded> {
+        let path = path.trim_start_matches(self.sep);
+
+        // a single probe when all routes are static
+        if let Some(paths) = self.static_paths() {
+            return Ok(match paths.get(path) {
+                Some(&state) if self.get_acceptance(state) => {
+                    Ok(Match::new(state, Vec::new(), false))
+                }
+                Some(_) => Err(RouteError::NoEndpoint),
+                None => Err(RouteError::NotFound),
+            });
+        }
 
-        // try fast path, only match static transition
-        if let Some(ret) = self.fast_path_search(path) {
-            return Some(ret);
+        // try fast path, only match static transition, fall through when the
+        // state reached is only a prefix of longer routes
+        if let Some(state) = self.fast_path_search(path.split(self.sep)) {
+            if self.get_acceptance(state) {
+                return Ok(Ok(Match::new(state, Vec::new(), false)));
+            }
         }
 
-        let mut roads = vec![Road::new(self.start_state(), Vec::new())];
-        while let Some((seg, reminder)) = path.split_once(CHAR_PATH_SEP) {
-            roads = self.process_seg(roads, seg, path);
-            path = reminder;
+        Ok(self
+            .best_road(path.split(self.sep), max_steps)?
+            .map(|(found, arena)| self.road_match(&arena, &found, path)))
+    }
+
+    /// Search the path given as segments, the same as searching them joined by `/`.
+    /// Wildcard values spanning several segments are joined again.
+    pub fn search_segments<'a: 'b, 'b>(
+        &'a self,
+        segs: &[&'b str],
+    ) -> Result<Match<'b, Cow<'b, str>>, RouteError> {
+        // leading empty segments are leading `/` of the joined path
+        let skip = segs.iter().take_while(|seg| seg.is_empty()).count();
+        let segs = match &segs[skip.min(segs.len().saturating_sub(1))..] {
+            [] => &[""],
+            segs => segs,
+        };
+
+        if let Some(state) = self.fast_path_search(segs.iter().copied()) {
+            if self.get_acceptance(state) {
+                return Ok(Match::new(state, Vec::new(), false));
+            }
         }
 
-        roads = self.process_seg(roads, path, path);
+        let (found, arena) = match self.best_road(segs.iter().copied(), usize::MAX) {
+            Ok(found) => found?,
+            Err(BudgetExceeded) => unreachable!("search is unbounded"),
+        };
+        let params = self.road_params(&arena, &found, |pos| match &segs[pos..] {
+            [seg] => Cow::Borrowed(*seg),
+            tail => Cow::Owned(tail.join(self.sep.encode_utf8(&mut [0; 4]))),
+        });
+
+        Ok(Match::new(found.state, params, found.wildcard))
+    }
+
+    /// Walk the segments, pick the most specific road reaching an accepted state.
+    fn best_road<'a: 'b, 'b>(
+        &'a self,
+        segs: impl Iterator<Item = &'b str>,
+        max_steps: usize,
+    ) -> Result<Result<(Road, Arena<'b>), RouteError>, BudgetExceeded> {
+        let (roads, arena) = self.roads(segs, max_steps)?;
+
+        if roads.is_empty() {
+            return Ok(Err(RouteError::NotFound));
+        }
 
         let roads = roads
             .into_iter()
@@ -245,7 +669,7 @@ impl Nfa {
         // detect longest path
         let found = roads.fold(None, |prev, curr| match prev {
             Some(item) => {
-                if item < curr {
+                if arena.cmp_roads(&item, &curr) == Some(std::cmp::Ordering::Less) {
                     Some(curr)
                 } else {
                     Some(item)
@@ -254,27 +678,167 @@ impl Nfa {
             None => Some(curr),
         });
 
-        found.map(|found| {
-            let mut params = Vec::new();
-            for capture in found.captures {
-                match capture {
-                    Capture::Param(n, v) => {
-                        params.push((n, v));
-                    }
-                    Capture::Wildcard(n, v) => {
-                        params.push((n, v));
+        let Some(found) = found else {
+            return Ok(Err(RouteError::NoEndpoint));
+        };
+
+        Ok(Ok((found, arena)))
+    }
+
+    /// Search all accepted routes matching the path, best first.
+    pub fn search_all<'a: 'b, 'b>(&'a self, path: &'b str) -> Vec<Match<'b>> {
+        let path = path.trim_start_matches(self.sep);
+
+        let (mut roads, arena) = self.accepted_roads(path);
+
+        // roads are only partially ordered, pick the best one repeatedly as `search` does
+        let mut matches = Vec::with_capacity(roads.len());
+        while !roads.is_empty() {
+            let mut best = 0;
+            for (i, road) in roads.iter().enumerate().skip(1) {
+                if arena.cmp_roads(&roads[best], road) == Some(std::cmp::Ordering::Less) {
+                    best = i;
+                }
+            }
+
+            matches.push(self.road_match(&arena, &roads.remove(best), path));
+        }
+
+        matches
+    }
+
+    /// Collect params of the road, named by the route it reaches.
+    fn road_match<'a: 'b, 'b>(
+        &'a self,
+        arena: &Arena<'b>,
+        found: &Road,
+        path: &'b str,
+    ) -> Match<'b> {
+        let params = self.road_params(arena, found, |pos| {
+            path.splitn(pos + 1, self.sep).last().unwrap_or(path)
+        });
+
+        Match::new(found.state, params, found.wildcard)
+    }
+
+    /// Params of the road, named by the route it reaches, the value of a wildcard
+    /// is built by `tail` from the position of its first segment.
+    fn road_params<'a: 'b, 'b, V: From<&'b str>>(
+        &'a self,
+        arena: &Arena<'b>,
+        found: &Road,
+        tail: impl Fn(usize) -> V,
+    ) -> Vec<(&'b str, V)> {
+        let mut params = Vec::new();
+        for capture in arena.captures(found) {
+            match capture {
+                Capture::Param(n, v) => {
+                    params.push((*n, V::from(v)));
+                }
+                Capture::Wildcard(n, pos) => {
+                    params.push((*n, tail(*pos)));
+                }
+                Capture::Static => {}
+            }
+        }
+
+        // names on shared edges belong to the first route, rename by the matched one,
+        // aligned to the tail since merged routes may lie under a prefix
+        if let Some(names) = self.names.get(&found.state) {
+            let offset = params.len().saturating_sub(names.len());
+            for (param, name) in params[offset..].iter_mut().zip(names) {
+                param.0 = name;
+            }
+        }
+
+        params
+    }
+
+    /// Search only with static transitions, params and wildcards are never matched.
+    pub fn search_static<'a: 'b, 'b>(&'a self, path: &'b str) -> Option<Match<'b>> {
+        let path = path.trim_start_matches(self.sep);
+        let state = match self.static_paths() {
+            Some(paths) => paths.get(path).copied(),
+            None => self.fast_path_search(path.split(self.sep)),
+        };
+
+        state
+            .filter(|state| self.get_acceptance(*state))
+            .map(|state| Match::new(state, Vec::new(), false))
+    }
+
+    /// Whether more than one accepted road matches the path with equal top specificity.
+    pub fn is_ambiguous(&self, path: &str) -> bool {
+        let path = path.trim_start_matches(self.sep);
+
+        let (roads, arena) = self.accepted_roads(path);
+
+        let best = roads
+            .iter()
+            .enumerate()
+            .fold(None, |prev: Option<(usize, &Road)>, curr| match prev {
+                Some(item) => {
+                    if arena.cmp_roads(item.1, curr.1) == Some(std::cmp::Ordering::Less) {
+                        Some(curr)
+                    } else {
+                        Some(item)
                     }
-                    Capture::Static => {}
                 }
+                None => Some(curr),
+            });
+
+        match best {
+            Some((index, best)) => roads.iter().enumerate().any(|(i, road)| {
+                i != index
+                    && matches!(
+                        arena.cmp_roads(best, road),
+                        None | Some(std::cmp::Ordering::Equal)
+                    )
+            }),
+            None => false,
+        }
+    }
+
+    /// Unbounded roads of the path reaching an accepted state.
+    fn accepted_roads<'a: 'b, 'b>(&'a self, path: &'b str) -> (Roads, Arena<'b>) {
+        let (roads, arena) = self
+            .roads(path.split(self.sep), usize::MAX)
+            .unwrap_or_default();
+        let roads = roads
+            .into_iter()
+            .filter(|road| self.get_acceptance(road.state))
+            .collect();
+
+        (roads, arena)
+    }
+
+    /// Walk all transitions for the segments, return every road reaching their end.
+    ///
+    /// Each road processing a segment takes a step, fail when more than `max_steps` taken.
+    /// Captures of all roads are kept in the returned arena, so branching never clones them.
+    fn roads<'a: 'b, 'b>(
+        &'a self,
+        segs: impl Iterator<Item = &'b str>,
+        max_steps: usize,
+    ) -> Result<(Roads, Arena<'b>), BudgetExceeded> {
+        let mut steps = 0usize;
+        let mut arena = Arena::default();
+        let mut roads: Roads = std::iter::once(Road::new(self.start_state())).collect();
+        for (pos, seg) in segs.enumerate() {
+            steps = steps.saturating_add(roads.len());
+            if steps > max_steps {
+                return Err(BudgetExceeded);
             }
 
-            Match::new(found.state, params)
-        })
+            roads = self.process_seg(&mut arena, roads, seg, pos);
+        }
+
+        Ok((roads, arena))
     }
 
-    fn fast_path_search(&self, path: &str) -> Option<Match> {
-        let mut road = Road::new(self.start_state(), Vec::new());
-        for seg in path.split(CHAR_PATH_SEP) {
+    fn fast_path_search<'b>(&self, segs: impl Iterator<Item = &'b str>) -> Option<usize> {
+        let mut road = Road::new(self.start_state());
+        for seg in segs {
             match self.process_static_seg(seg, road) {
                 Some(r) => {
                     road = r;
@@ -285,13 +849,13 @@ impl Nfa {
             }
         }
 
-        Some(Match::new(road.state, Vec::new()))
+        Some(road.state)
     }
 
-    fn process_static_seg<'a: 'b, 'b>(&'a self, seg: &str, mut road: Road<'b>) -> Option<Road<'b>> {
+    fn process_static_seg(&self, seg: &str, mut road: Road) -> Option<Road> {
         self.get_state(road.state)
             .transitions
-            .capture_static(seg)
+            .capture_static(&self.static_key(seg), self.case_insensitive)
             .map(|next| {
                 road.state = next;
                 road
@@ -300,11 +864,12 @@ impl Nfa {
 
     fn process_seg<'a: 'b, 'b>(
         &'a self,
-        roads: Vec<Road<'a>>,
+        arena: &mut Arena<'b>,
+        roads: Roads,
         seg: &'b str,
-        path: &'b str,
-    ) -> Vec<Road<'b>> {
-        let mut returned = Vec::with_capacity(roads.len());
+        pos: usize,
+    ) -> Roads {
+        let mut returned = Roads::with_capacity(roads.len());
 
         for r in roads {
             // while into wildcard, skip it
@@ -313,45 +878,57 @@ impl Nfa {
                 continue;
             }
 
-            let Road {
-                state, captures, ..
-            } = r;
-
-            for (capture, next) in self.get_state(state).transitions.capture(seg, path) {
-                let mut new_captures = captures.clone();
-                match capture {
-                    Capture::Wildcard(_name, _param) => {
-                        new_captures.push(capture);
-                        let mut road = Road::new(next, new_captures);
-                        road.set_wildcard(true);
-                        returned.push(road);
-                    }
-                    _ => {
-                        new_captures.push(capture);
-                        returned.push(Road::new(next, new_captures));
-                    }
+            let key = self.static_key(seg);
+            let reserved = self.reserved.contains(&key, self.case_insensitive);
+            let transitions = &self.get_state(r.state).transitions;
+
+            for (capture, next) in transitions.capture(&key, self.case_insensitive, seg, pos) {
+                if reserved && matches!(capture, Capture::Param(_, _)) {
+                    continue;
                 }
+
+                let wildcard = matches!(capture, Capture::Wildcard(_, _));
+                let mut road = r.step(next, arena.push(capture, r.tail));
+                road.set_wildcard(wildcard);
+                returned.push(road);
             }
         }
 
         returned
     }
 
-    pub(crate) fn merge(&mut self, left: usize, other: &Self, right: usize) -> Vec<(usize, usize)> {
+    /// Graft the states of `other` below `right` under the `left` state, routes of `other`
+    /// are named after `prefix`, the names of the params leading to `left`.
+    pub(crate) fn merge(
+        &mut self,
+        left: usize,
+        other: &Self,
+        right: usize,
+        prefix: &[String],
+    ) -> Vec<(usize, usize)> {
         let mut returned = Vec::new();
 
         for Entry { pat, index: old } in other.get_state(right).transitions.entries() {
-            let new_state = self.new_state();
+            // reuse the existing transition, so routes already in place are kept
+            let new_state = match self.get_state(left).transitions.get(&pat) {
+                Some(s) => s,
+                None => {
+                    let new_state = self.new_state(left);
+                    self.get_state_mut(left).transitions.push(pat, new_state);
+                    new_state
+                }
+            };
             if other.get_acceptance(old) {
                 self.accept(new_state);
             }
-            self.get_state_mut(left)
-                .transitions
-                .push(pat.clone(), new_state);
+            if let Some(names) = other.names.get(&old) {
+                let names = prefix.iter().chain(names).cloned().collect();
+                self.names.insert(new_state, names);
+            }
 
             returned.push((new_state, old));
 
-            returned.extend(self.merge(new_state, other, old));
+            returned.extend(self.merge(new_state, other, old, prefix));
         }
 
         returned
@@ -359,14 +936,24 @@ impl Nfa {
 }
 
 #[derive(Debug)]
-pub struct Match<'a> {
+pub struct Match<'a, V = &'a str> {
     pub state: usize,
-    pub params: Vec<(&'a str, &'a str)>,
+    pub params: Vec<(&'a str, V)>,
+    /// Whether the road ended in a wildcard.
+    pub wildcard: bool,
 }
 
-impl<'a> Match<'a> {
-    fn new(state: usize, params: Vec<(&'a str, &'a str)>) -> Self {
-        Match { state, params }
+impl<'a, V> Match<'a, V> {
+    fn new(state: usize, params: Vec<(&'a str, V)>, wildcard: bool) -> Self {
+        Match {
+            state,
+            params,
+            wildcard,
+        }
+    }
+
+    pub fn kind(&self) -> MatchKind {
+        MatchKind::found(self.wildcard, !self.params.is_empty())
     }
 }
 
@@ -374,56 +961,112 @@ impl<'a> Match<'a> {
 enum Capture<'a> {
     Static,
     Param(&'a str, &'a str),
-    Wildcard(&'a str, &'a str),
+    /// Wildcard name, along with the position of the first segment of its tail.
+    Wildcard(&'a str, usize),
+}
+
+impl Capture<'_> {
+    /// Specificity of the capture against another at the same position,
+    /// static over param over wildcard.
+    fn partial_cmp_kind(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        use std::cmp::Ordering::{Greater, Less};
+
+        match (self, other) {
+            (Capture::Static, Capture::Param(_, _))
+            | (Capture::Static, Capture::Wildcard(_, _)) => Some(Greater),
+            (Capture::Param(_, _), Capture::Static)
+            | (Capture::Wildcard(_, _), Capture::Static) => Some(Less),
+            (Capture::Param(_, _), Capture::Wildcard(_, _)) => Some(Greater),
+            (Capture::Wildcard(_, _), Capture::Param(_, _)) => Some(Less),
+            _ => None,
+        }
+    }
+}
+
+/// Captures of all roads of a search, each links to the previous capture of its road,
+/// so roads branching from the same state share their common captures.
+#[derive(Debug, Default)]
+struct Arena<'a> {
+    captures: Vec<(Capture<'a>, Option<usize>)>,
+}
+
+impl<'a> Arena<'a> {
+    fn push(&mut self, capture: Capture<'a>, prev: Option<usize>) -> usize {
+        self.captures.push((capture, prev));
+        self.captures.len() - 1
+    }
+
+    /// Captures of the road, in path order.
+    fn captures(&self, road: &Road) -> Vec<&Capture<'a>> {
+        let mut captures: Vec<&Capture<'a>> = self.chain(road.tail).collect();
+        captures.reverse();
+        captures
+    }
+
+    /// Captures from the tail backwards.
+    fn chain(&self, tail: Option<usize>) -> impl Iterator<Item = &Capture<'a>> {
+        std::iter::successors(tail, |index| self.captures[*index].1)
+            .map(|index| &self.captures[index].0)
+    }
+
+    /// Order roads by length, then by the specificity of their first differing capture,
+    /// `None` when neither is more specific.
+    fn cmp_roads(&self, a: &Road, b: &Road) -> Option<std::cmp::Ordering> {
+        if a.len != b.len {
+            return a.len.partial_cmp(&b.len);
+        }
+
+        // walking backwards, the last difference seen is the first in path order,
+        // shared captures end the walk early
+        let (mut left, mut right) = (a.tail, b.tail);
+        let mut ordering = None;
+        while let (Some(l), Some(r)) = (left, right) {
+            if l == r {
+                break;
+            }
+
+            let (lc, lp) = &self.captures[l];
+            let (rc, rp) = &self.captures[r];
+            if let Some(o) = lc.partial_cmp_kind(rc) {
+                ordering = Some(o);
+            }
+            (left, right) = (*lp, *rp);
+        }
+
+        ordering
+    }
 }
 
-#[derive(Debug, PartialEq)]
-struct Road<'a> {
+/// A road walked through the states, its captures live in the [`Arena`].
+#[derive(Debug, Clone, Copy)]
+struct Road {
     state: usize,
-    captures: Vec<Capture<'a>>,
+    tail: Option<usize>,
+    len: usize,
     wildcard: bool,
 }
 
-impl<'a> Road<'a> {
-    fn new(state: usize, captures: Vec<Capture<'a>>) -> Self {
+impl Road {
+    fn new(state: usize) -> Self {
         Road {
             state,
-            captures,
+            tail: None,
+            len: 0,
             wildcard: false,
         }
     }
 
-    fn set_wildcard(&mut self, wildcard: bool) {
-        self.wildcard = wildcard;
+    fn step(&self, state: usize, tail: usize) -> Self {
+        Road {
+            state,
+            tail: Some(tail),
+            len: self.len + 1,
+            wildcard: false,
+        }
     }
-}
 
-impl<'a> PartialOrd for Road<'a> {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self.captures.len() == other.captures.len() {
-            for (a, b) in self.captures.iter().zip(other.captures.iter()) {
-                match (a, b) {
-                    (Capture::Static, Capture::Param(_, _))
-                    | (Capture::Static, Capture::Wildcard(_, _)) => {
-                        return Some(std::cmp::Ordering::Greater)
-                    }
-                    (Capture::Param(_, _), Capture::Static)
-                    | (Capture::Wildcard(_, _), Capture::Static) => {
-                        return Some(std::cmp::Ordering::Less)
-                    }
-                    (Capture::Param(_, _), Capture::Wildcard(_, _)) => {
-                        return Some(std::cmp::Ordering::Greater)
-                    }
-                    (Capture::Wildcard(_, _), Capture::Param(_, _)) => {
-                        return Some(std::cmp::Ordering::Less)
-                    }
-                    _ => continue,
-                }
-            }
-            None
-        } else {
-            self.captures.len().partial_cmp(&other.captures.len())
-        }
+    fn set_wildcard(&mut self, wildcard: bool) {
+        self.wildcard = wildcard;
     }
 }
 
@@ -477,7 +1120,7 @@ mod test {
 
         let sub = nfa.locate("/a");
 
-        nfa.merge(sub, &other, other.start_state());
+        nfa.merge(sub, &other, other.start_state(), &[]);
 
         println!("-> {:?}", nfa);
 
@@ -485,4 +1128,184 @@ mod test {
 
         println!("ret => {:?}", ret);
     }
+
+    #[test]
+    fn test_nfa_find_state() {
+        let mut nfa = Nfa::new();
+
+        let state = nfa.insert("/posts/:id");
+        let count = nfa.states.len();
+
+        assert_eq!(nfa.find_state("/posts/:id"), Some(state));
+        assert_eq!(nfa.find_state("/posts/:name"), Some(state));
+        assert_eq!(nfa.find_state("/posts/:id/comments"), None);
+        assert_eq!(nfa.find_state("/users"), None);
+        assert_eq!(nfa.states.len(), count);
+
+        assert!(nfa.reject(state));
+        assert!(!nfa.reject(state));
+        assert!(nfa.search("/posts/1").is_err());
+    }
+
+    #[test]
+    fn test_nfa_ambiguous() {
+        let mut nfa = Nfa::new();
+
+        nfa.insert("/posts/:id");
+        nfa.insert("/posts/new");
+
+        assert!(!nfa.is_ambiguous("/posts/1"));
+        assert!(!nfa.is_ambiguous("/posts/new"));
+        assert!(!nfa.is_ambiguous("/users"));
+
+        // craft a second param transition next to `:id`
+        let posts = nfa.find_state("/posts").unwrap();
+        let other = nfa.new_state(posts);
+        nfa.accept(other);
+        nfa.get_state_mut(posts)
+            .transitions
+            .push(Pattern::Param("name".to_string(), None), other);
+
+        assert!(nfa.is_ambiguous("/posts/1"));
+        assert!(!nfa.is_ambiguous("/posts/new"));
+    }
+
+    #[test]
+    fn test_nfa_anonymous() {
+        let mut nfa = Nfa::new();
+
+        let anonymous = nfa.insert("/a/:/c");
+        let fixed = nfa.insert("/a/b/c");
+        let named = nfa.insert("/a/:name/d");
+
+        let found = nfa.search("/a/b/c").unwrap();
+        assert_eq!(found.state, fixed);
+        assert!(found.params.is_empty());
+
+        // anonymous param still takes a segment
+        let found = nfa.search("/a/x/c").unwrap();
+        assert_eq!(found.state, anonymous);
+        assert_eq!(found.params, vec![("", "x")]);
+        assert!(nfa.search("/a/c").is_err());
+
+        let found = nfa.search("/a/x/d").unwrap();
+        assert_eq!(found.state, named);
+        assert_eq!(found.params, vec![("name", "x")]);
+        assert_eq!(nfa.pattern(anonymous), "/a/:/c");
+        assert_eq!(nfa.pattern(named), "/a/:name/d");
+    }
+
+    #[test]
+    fn test_nfa_param_branches() {
+        let mut nfa = Nfa::new();
+
+        let params = nfa.insert("/:a/:b/:c/:d");
+        let static_d = nfa.insert("/:a/:b/:c/d");
+        let static_b = nfa.insert("/:a/b/:c/:d");
+        let wildcard = nfa.insert("/:a/b/*rest");
+
+        // the earliest static segment wins among roads of the same length
+        let found = nfa.search("/a/b/c/d").unwrap();
+        assert_eq!(found.state, static_b);
+        assert_eq!(found.params, vec![("a", "a"), ("c", "c"), ("d", "d")]);
+
+        let found = nfa.search("/a/x/c/d").unwrap();
+        assert_eq!(found.state, static_d);
+        assert_eq!(found.params, vec![("a", "a"), ("b", "x"), ("c", "c")]);
+
+        let found = nfa.search("/a/x/c/y").unwrap();
+        assert_eq!(found.state, params);
+
+        let found = nfa.search("/a/b/c/d/e").unwrap();
+        assert_eq!(found.state, wildcard);
+        assert_eq!(found.params, vec![("a", "a"), ("rest", "c/d/e")]);
+
+        let all: Vec<usize> = nfa.search_all("/a/b/c/d").iter().map(|m| m.state).collect();
+        assert_eq!(all, vec![static_b, static_d, params, wildcard]);
+    }
+
+    #[test]
+    fn test_nfa_shared_captures() {
+        let mut nfa = Nfa::new();
+
+        nfa.insert("/:a/:b/x");
+        nfa.insert("/:a/:b/y");
+        nfa.insert("/:a/:b/:c");
+
+        let (roads, arena) = nfa.roads("p/q/x".split('/'), usize::MAX).unwrap();
+        assert_eq!(roads.len(), 2);
+
+        // one capture per transition taken, branching roads share the common ones
+        assert_eq!(arena.captures.len(), 4);
+        let parents: Vec<Option<usize>> = roads
+            .iter()
+            .map(|road| arena.captures[road.tail.unwrap()].1)
+            .collect();
+        assert_eq!(parents[0], parents[1]);
+        assert_eq!(
+            arena.cmp_roads(&roads[0], &roads[1]),
+            Some(std::cmp::Ordering::Greater)
+        );
+    }
+
+    #[test]
+    fn test_nfa_static_paths() {
+        let mut nfa = Nfa::new();
+
+        let root = nfa.insert("/");
+        let posts = nfa.insert("/posts/new");
+        let slash = nfa.insert("/posts/new/");
+        assert_eq!(nfa.static_paths().unwrap().len(), 4);
+
+        assert_eq!(nfa.search("/").unwrap().state, root);
+        assert_eq!(nfa.search("/posts/new").unwrap().state, posts);
+        assert_eq!(nfa.search("posts/new/").unwrap().state, slash);
+        assert_eq!(nfa.search("/posts").unwrap_err(), RouteError::NoEndpoint);
+        assert_eq!(nfa.search("/posts/old").unwrap_err(), RouteError::NotFound);
+
+        // any param route falls back to walking the states
+        let id = nfa.insert("/posts/:id");
+        assert!(nfa.static_paths().is_none());
+        assert_eq!(nfa.search("/posts/new").unwrap().state, posts);
+        assert_eq!(nfa.search("/posts/old").unwrap().state, id);
+
+        nfa.clear();
+        let users = nfa.insert("/users");
+        assert!(nfa.static_paths().is_some());
+        assert_eq!(nfa.search("/users").unwrap().state, users);
+
+        nfa.set_case_insensitive(true);
+        assert!(nfa.static_paths().is_none());
+        assert_eq!(nfa.search("/USERS").unwrap().state, users);
+    }
+
+    #[test]
+    fn test_nfa_reserve() {
+        let mut nfa = Nfa::new();
+
+        nfa.reserve(64);
+        assert!(nfa.states.capacity() >= 65);
+        assert!(nfa.acceptances.capacity() >= 65);
+
+        let capacity = nfa.states.capacity();
+        let post = nfa.insert("/posts/:id/comments/:cid");
+        assert_eq!(nfa.states.capacity(), capacity);
+        assert_eq!(nfa.search("/posts/1/comments/2").unwrap().state, post);
+    }
+
+    #[test]
+    fn test_nfa_clear() {
+        let mut nfa = Nfa::new();
+
+        nfa.insert("/posts/:id/comments/:cid");
+        let capacity = nfa.states.capacity();
+        nfa.clear();
+
+        assert_eq!(nfa.states.len(), 1);
+        assert_eq!(nfa.states.capacity(), capacity);
+        assert!(nfa.search("/posts/1/comments/2").is_err());
+
+        let users = nfa.insert("/users/:id");
+        assert_eq!(nfa.search("/users/1").unwrap().state, users);
+    }
 }
@@ -1,10 +1,20 @@
-use std::collections::BTreeMap;
+use std::{borrow::Cow, collections::BTreeMap, fmt::Write};
+
+use crate::{
+    constraint::{Constraint, Reserved},
+    percent, MatchKind, RouteError, RouterStats,
+};
 
 pub(crate) type ParamMap = BTreeMap<usize, (String, String)>;
+pub(crate) type ParamRefs<'a> = Vec<(&'a str, &'a str)>;
+/// Segment walked at each depth, along with the path remained from it.
+type Steps<'a> = Vec<(&'a str, &'a str)>;
 
 const CHAR_PATH_SEP: char = '/';
 const CHAR_PARAM: char = ':';
 const CHAR_WILDCARD: char = '*';
+const CHAR_ESCAPE: char = '\\';
+const CHAR_OPTIONAL: char = '?';
 
 const PAT_PATH_SEP: &str = "/";
 const PAT_PARAM: &str = ":";
@@ -13,7 +23,7 @@ const PAT_WILDCARD: &str = "*";
 #[derive(Debug, Clone, PartialEq)]
 enum Pattern {
     Static(String),
-    Param(String),
+    Param(String, Option<Constraint>),
     Wildcard(String),
 }
 
@@ -21,17 +31,51 @@ impl Pattern {
     fn from_str(pat: impl AsRef<str>) -> Self {
         let pat = pat.as_ref();
         match pat.chars().next() {
-            Some(CHAR_PARAM) => Pattern::Param(pat[1..].to_owned()),
+            // `\:name` and `\*name` are literal segments
+            Some(CHAR_ESCAPE) if pat[1..].starts_with([CHAR_PARAM, CHAR_WILDCARD, CHAR_ESCAPE]) => {
+                Pattern::Static(pat[1..].to_owned())
+            }
+            Some(CHAR_PARAM) => {
+                let param = &pat[1..];
+                let param = param.strip_suffix(CHAR_OPTIONAL).unwrap_or(param);
+                let (name, constraint) = Constraint::parse(param);
+                Pattern::Param(name.to_owned(), constraint)
+            }
             Some(CHAR_WILDCARD) => Pattern::Wildcard(pat[1..].to_owned()),
             _ => Pattern::Static(pat.to_owned()),
         }
     }
 
-    fn as_pat(&self) -> &str {
+    fn as_pat(&self) -> Cow<'_, str> {
         match self {
-            Pattern::Param(_) => PAT_PARAM,
-            Pattern::Wildcard(_) => PAT_WILDCARD,
-            Pattern::Static(p) => p,
+            Pattern::Param(_, None) => Cow::Borrowed(PAT_PARAM),
+            Pattern::Param(_, Some(c)) => Cow::Owned(format!("{PAT_PARAM}{c}")),
+            Pattern::Wildcard(_) => Cow::Borrowed(PAT_WILDCARD),
+            Pattern::Static(p) => Pattern::static_key(p),
+        }
+    }
+
+    /// Key of a static child, literals looking like params or wildcards keep their escape,
+    /// so they never collide with the `:` and `*` keys.
+    fn static_key(seg: &str) -> Cow<'_, str> {
+        if seg.starts_with([CHAR_PARAM, CHAR_WILDCARD, CHAR_ESCAPE]) {
+            Cow::Owned(format!("{CHAR_ESCAPE}{seg}"))
+        } else {
+            Cow::Borrowed(seg)
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Static(p) if p.starts_with([CHAR_PARAM, CHAR_WILDCARD, CHAR_ESCAPE]) => {
+                write!(f, "{CHAR_ESCAPE}{p}")
+            }
+            Pattern::Static(p) => write!(f, "{p}"),
+            Pattern::Param(p, None) => write!(f, "{CHAR_PARAM}{p}"),
+            Pattern::Param(p, Some(c)) => write!(f, "{CHAR_PARAM}{p}{c}"),
+            Pattern::Wildcard(p) => write!(f, "{CHAR_WILDCARD}{p}"),
         }
     }
 }
@@ -50,6 +94,10 @@ pub(crate) struct Node<T> {
     children: BTreeMap<String, usize>,
     has_param_child: bool,
     has_wildcard_child: bool,
+    /// Param names of the route ending here, nodes are shared regardless of names.
+    names: Vec<String>,
+    /// The param of the node may be omitted, as the last segment of a route.
+    optional: bool,
     pub(crate) data: Option<T>,
 }
 
@@ -62,6 +110,8 @@ impl<T> Node<T> {
             children: BTreeMap::new(),
             has_param_child: false,
             has_wildcard_child: false,
+            names: Vec::new(),
+            optional: false,
             data: None,
         }
     }
@@ -70,102 +120,406 @@ impl<T> Node<T> {
 #[derive(Debug, Clone)]
 pub struct Tree<T> {
     nodes: Vec<Node<T>>,
+    /// Segments never captured by params.
+    reserved: Reserved,
+    decode_static: bool,
+    case_insensitive: bool,
+    /// Separator of path segments.
+    sep: char,
 }
 
 impl<T> Tree<T> {
     pub fn new() -> Self {
         let root = Node::new(0, 0, Pattern::from_str(PAT_PATH_SEP));
 
-        Tree { nodes: vec![root] }
+        Tree {
+            nodes: vec![root],
+            reserved: Reserved::default(),
+            decode_static: false,
+            case_insensitive: false,
+            sep: CHAR_PATH_SEP,
+        }
+    }
+
+    /// Set the separator of path segments, which applies to patterns and paths alike.
+    pub fn set_separator(&mut self, sep: char) {
+        self.sep = sep;
+    }
+
+    pub fn separator(&self) -> char {
+        self.sep
+    }
+
+    /// Segments which params never capture, they only match static children.
+    pub fn set_reserved<S: ToString>(&mut self, words: impl IntoIterator<Item = S>) {
+        self.reserved = Reserved::new(words);
+    }
+
+    /// Match static children ASCII case-insensitively, static segments inserted
+    /// afterwards are stored lowercased.
+    pub fn set_case_insensitive(&mut self, enable: bool) {
+        self.case_insensitive = enable;
+    }
+
+    fn segment_pattern(&self, seg: &str) -> Pattern {
+        match Pattern::from_str(seg) {
+            Pattern::Static(p) if self.case_insensitive => Pattern::Static(p.to_ascii_lowercase()),
+            pat => pat,
+        }
+    }
+
+    /// Number of nodes holding data.
+    pub fn len(&self) -> usize {
+        self.nodes.iter().filter(|node| node.data.is_some()).count()
+    }
+
+    /// Reserve room for at least `additional` more nodes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Reset to a single root node, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.nodes
+            .push(Node::new(0, 0, Pattern::from_str(PAT_PATH_SEP)));
+    }
+
+    /// Percent-decode segments before matching static children.
+    pub fn set_decode_static(&mut self, enable: bool) {
+        self.decode_static = enable;
     }
 
     pub fn insert(&mut self, path: &str, data: T) {
-        let got = self.at(path);
+        let got = self.at_route(path);
 
         got.data = Some(data);
     }
 
-    pub fn search(&self, path: &str) -> Option<(&T, ParamMap)> {
-        match self.search_node(path) {
-            Some(node) => {
-                let params = self.capture_params(path, node);
+    pub fn search(&self, path: &str) -> Result<(&T, ParamMap, MatchKind), RouteError> {
+        let (node, params, kind) = self.search_params(path)?;
+
+        self.get(node)
+            .data
+            .as_ref()
+            .map(|data| (data, params, kind))
+            .ok_or(RouteError::NoEndpoint)
+    }
+
+    /// Flatten into the immutable layout, nodes renumbered breadth-first.
+    pub fn freeze(self) -> Frozen<T> {
+        // children follow their key order, so siblings stay sorted
+        let mut order = vec![0];
+        let mut i = 0;
+        while let Some(&index) = order.get(i) {
+            order.extend(self.nodes[index].children.values());
+            i += 1;
+        }
 
-                self.get(node).data.as_ref().map(|data| (data, params))
+        let mut renumbered = vec![0; self.nodes.len()];
+        for (new, old) in order.iter().enumerate() {
+            renumbered[*old] = new;
+        }
+
+        let mut frozen = Frozen {
+            patterns: Vec::with_capacity(order.len()),
+            parents: Vec::with_capacity(order.len()),
+            offsets: Vec::with_capacity(order.len() + 1),
+            children: Vec::with_capacity(order.len().saturating_sub(1)),
+            flags: Vec::with_capacity(order.len()),
+            names: Vec::with_capacity(order.len()),
+            data: Vec::with_capacity(order.len()),
+            reserved: self.reserved,
+            decode_static: self.decode_static,
+            case_insensitive: self.case_insensitive,
+            sep: self.sep,
+        };
+
+        let mut nodes: Vec<Option<Node<T>>> = self.nodes.into_iter().map(Some).collect();
+        for old in order {
+            let node = nodes[old].take().expect("node visited twice");
+
+            let mut flags = 0;
+            if node.has_param_child {
+                flags |= Frozen::<T>::PARAM_CHILD;
+            }
+            if node.has_wildcard_child {
+                flags |= Frozen::<T>::WILDCARD_CHILD;
+            }
+            if node.optional {
+                flags |= Frozen::<T>::OPTIONAL;
             }
 
-            None => None,
+            frozen.offsets.push(frozen.children.len());
+            frozen.children.extend(
+                node.children
+                    .into_iter()
+                    .map(|(key, child)| (key, renumbered[child])),
+            );
+            frozen.patterns.push(node.pattern);
+            frozen.parents.push(renumbered[node.parent]);
+            frozen.flags.push(flags);
+            frozen.names.push(node.names);
+            frozen.data.push(node.data);
         }
+        frozen.offsets.push(frozen.children.len());
+
+        frozen
     }
 
-    pub fn merge(&mut self, path: &str, other: Self) {
-        let offset = self.nodes.len() - 1;
+    /// Search the path, along with the pattern of the matched node.
+    pub fn search_pattern(
+        &self,
+        path: &str,
+    ) -> Result<(&T, ParamMap, MatchKind, String), RouteError> {
+        let (node, params, kind) = self.search_params(path)?;
+
+        self.get(node)
+            .data
+            .as_ref()
+            .map(|data| (data, params, kind, self.pattern(node)))
+            .ok_or(RouteError::NoEndpoint)
+    }
 
-        let path = path.trim_end_matches('/');
+    /// Whether data is stored at the node for the path.
+    pub fn contains(&self, path: &str) -> bool {
+        self.find(path)
+            .is_some_and(|node| self.get(node).data.is_some())
+    }
 
-        let root = self.at(path).index;
+    /// Graphviz digraph of the tree, nodes holding data are drawn as double circles,
+    /// edges are labeled by the child key.
+    pub fn to_dot(&self) -> String {
+        fn escape(label: &str) -> String {
+            label.replace('\\', "\\\\").replace('"', "\\\"")
+        }
 
-        for mut n in other.nodes {
-            // skip root
-            if n.index == 0 {
-                continue;
-            }
+        let mut dot = String::from("digraph tree {\n");
 
-            if n.parent == 0 {
-                n.parent = root;
+        // writing into a string never fails
+        for node in &self.nodes {
+            let shape = if node.data.is_some() {
+                "doublecircle"
             } else {
-                n.parent = offset;
+                "circle"
+            };
+            let _ = writeln!(
+                dot,
+                "    n{} [label=\"{}\", shape={shape}];",
+                node.index,
+                escape(&node.pattern.to_string())
+            );
+        }
+        for node in &self.nodes {
+            for (key, child) in &node.children {
+                let _ = writeln!(
+                    dot,
+                    "    n{} -> n{child} [label=\"{}\"];",
+                    node.index,
+                    escape(key)
+                );
             }
+        }
 
-            let child = self.add_child(n.parent, n.pattern);
+        dot.push_str("}\n");
+        dot
+    }
 
-            self.get_mut(child).data = n.data;
+    /// Size of the tree, nodes are created after their parent.
+    pub fn stats(&self) -> RouterStats {
+        let mut depths = vec![0; self.nodes.len()];
+        for node in self.nodes.iter().skip(1) {
+            depths[node.index] = depths[node.parent] + 1;
+        }
+
+        RouterStats {
+            state_count: self.nodes.len(),
+            accepting_count: self.len(),
+            max_depth: depths.into_iter().max().unwrap_or_default(),
+            param_edge_count: self
+                .nodes
+                .iter()
+                .filter(|n| matches!(n.pattern, Pattern::Param(_, _)))
+                .count(),
         }
     }
 
-    fn search_node(&self, path: &str) -> Option<usize> {
+    /// Find the node conflicting with the path, either the node of the same route
+    /// holding data, or a param or wildcard at the same position named differently.
+    pub fn find_conflict(&self, path: &str) -> Option<usize> {
         let mut node = self.nodes.first().unwrap().index;
 
-        let mut segs = Segments::new(path);
+        let mut segs = Segments::new(path, self.sep);
 
         while let Some(seg) = segs.next() {
-            match self.search_child(node, seg) {
-                Some(n) => {
-                    if let Pattern::Wildcard(_) = &self.get(n).pattern {
-                        // when wildcard, return
-                        return Some(n);
-                    }
+            let pat = self.segment_pattern(seg);
+            let next = self.get_child(node, &pat)?;
 
-                    node = n;
-                }
-                None => match self.search_closest_wildcard_node(node) {
-                    Some(n) => {
-                        node = n;
-
-                        break;
-                    }
-                    None => {
-                        return None;
-                    }
-                },
+            match (&self.get(next).pattern, &pat) {
+                (Pattern::Param(l, _), Pattern::Param(r, _)) if l != r => return Some(next),
+                (Pattern::Wildcard(l), Pattern::Wildcard(r)) if l != r => return Some(next),
+                _ => {}
             }
+
+            node = next;
         }
 
-        if self.get(node).data.is_none() {
-            if let Some(n) = self.search_closest_wildcard_node(node) {
-                node = n;
+        self.get(node).data.as_ref().map(|_| node)
+    }
+
+    /// Clear data of the node for the path, the tree structure is left intact.
+    pub fn remove(&mut self, path: &str) -> Option<T> {
+        let node = self.find(path)?;
+
+        self.get_mut(node).data.take()
+    }
+
+    /// Search the path, params are borrowed from the tree and the path.
+    pub fn search_ref<'a: 'b, 'b>(
+        &'a self,
+        path: &'b str,
+    ) -> Result<(&'a T, ParamRefs<'b>), RouteError> {
+        let (node, steps, _wildcard) = self.search_node(path)?;
+        let params = self
+            .captures(node, &steps)
+            .into_iter()
+            .filter(|(_index, n, _v)| !n.is_empty())
+            .map(|(_index, n, v)| (n, v))
+            .collect();
+
+        self.get(node)
+            .data
+            .as_ref()
+            .map(|data| (data, params))
+            .ok_or(RouteError::NoEndpoint)
+    }
+
+    /// Graft the nodes of `other` under the path, data of `other` overwrites
+    /// data already at the same node. Params of the path are captured by the
+    /// merged routes too. Return the old and new pattern of each node with data,
+    /// in node order like `routes`.
+    pub fn merge(&mut self, path: &str, other: Self) -> Vec<(String, String)> {
+        let path = path.trim_end_matches(self.sep);
+
+        let root = self.at(path).index;
+        let prefix = self.route_names(path);
+
+        // nodes of `other` mapped to nodes of this tree, parents always come first
+        let mut mapping = vec![root; other.nodes.len()];
+        let mut patterns: Vec<String> = other.nodes[1..]
+            .iter()
+            .rev()
+            .filter(|n| n.data.is_some())
+            .map(|n| other.pattern(n.index))
+            .collect();
+        let mut mapped = Vec::with_capacity(patterns.len());
+
+        for n in other.nodes {
+            // skip root
+            if n.index == 0 {
+                continue;
+            }
+
+            let child = self.add_child(mapping[n.parent], n.pattern);
+            mapping[n.index] = child;
+
+            if n.data.is_some() {
+                let node = self.get_mut(child);
+                node.names = prefix.iter().cloned().chain(n.names).collect();
+                node.optional = n.optional;
+                node.data = n.data;
+                mapped.extend(patterns.pop().map(|old| (old, self.pattern(child))));
             }
         }
 
-        self.get(node).data.as_ref().map(|_| node)
+        mapped
+    }
+
+    /// Iterate all nodes with data, along with their reconstructed pattern.
+    pub fn routes(&self) -> impl Iterator<Item = (String, &T)> {
+        self.nodes.iter().filter_map(move |node| {
+            node.data
+                .as_ref()
+                .map(|data| (self.pattern(node.index), data))
+        })
+    }
+
+    /// Keys of the children below the node reached by the prefix, static segments
+    /// as written, params as `:` (with their constraint) and wildcards as `*`.
+    /// Empty when the prefix leads nowhere.
+    pub fn children_of(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.trim_matches(self.sep);
+        let node = if prefix.is_empty() {
+            Some(0)
+        } else {
+            self.find(prefix)
+        };
+
+        node.map(|node| self.get(node).children.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Reconstruct the pattern string leading to the node, route names are aligned
+    /// to the tail. Only the returned string is allocated.
+    pub fn pattern(&self, node: usize) -> String {
+        let names = &self.get(node).names;
+        let dynamic = std::iter::successors(Some(node), |i| (*i != 0).then(|| self.get(*i).parent))
+            .filter(|i| *i != 0 && !matches!(self.get(*i).pattern, Pattern::Static(_)))
+            .count();
+        let skip = dynamic.saturating_sub(names.len());
+        let names = &names[names.len().saturating_sub(dynamic)..];
+
+        let mut pattern = String::new();
+        self.write_pattern(node, node, names, skip, &mut 0, &mut pattern);
+
+        pattern
+    }
+
+    /// Write the segments from the root down to `index`, `dynamic` counts the params
+    /// and wildcards written, the first `skip` keep their node names.
+    fn write_pattern(
+        &self,
+        index: usize,
+        last: usize,
+        names: &[String],
+        skip: usize,
+        dynamic: &mut usize,
+        pattern: &mut String,
+    ) {
+        if index == 0 {
+            return;
+        }
+
+        let node = self.get(index);
+        self.write_pattern(node.parent, last, names, skip, dynamic, pattern);
+
+        pattern.push(self.sep);
+        let name = match &node.pattern {
+            Pattern::Static(_) => None,
+            _ => {
+                *dynamic += 1;
+                (*dynamic - 1).checked_sub(skip).map(|i| names[i].as_str())
+            }
+        };
+        // writing into a string never fails
+        let _ = match (&node.pattern, name) {
+            (Pattern::Param(_, None), Some(name)) => write!(pattern, "{CHAR_PARAM}{name}"),
+            (Pattern::Param(_, Some(c)), Some(name)) => write!(pattern, "{CHAR_PARAM}{name}{c}"),
+            (Pattern::Wildcard(_), Some(name)) => write!(pattern, "{CHAR_WILDCARD}{name}"),
+            (pat, _) => write!(pattern, "{pat}"),
+        };
+        if node.optional && index == last {
+            pattern.push(CHAR_OPTIONAL);
+        }
     }
 
     pub(crate) fn at(&mut self, path: &str) -> &mut Node<T> {
         let mut node = self.nodes.first().unwrap().index;
 
-        let mut segs = Segments::new(path);
+        let mut segs = Segments::new(path, self.sep);
 
         while let Some(seg) = segs.next() {
-            let pat = Pattern::from_str(seg);
+            let pat = self.segment_pattern(seg);
 
             match self.get_child(node, &pat) {
                 Some(n) => {
@@ -180,6 +534,50 @@ impl<T> Tree<T> {
         self.get_mut(node)
     }
 
+    /// Like `at`, also record param names of the path as a route.
+    ///
+    /// A last param suffixed with `?` is optional, the route also matches without it.
+    /// Panics on an optional wildcard, which matches an empty tail anyway.
+    pub(crate) fn at_route(&mut self, path: &str) -> &mut Node<T> {
+        let last = path.rsplit(self.sep).next().unwrap_or_default();
+        let optional = last.starts_with(CHAR_PARAM) && last.ends_with(CHAR_OPTIONAL);
+        assert!(
+            !(last.starts_with(CHAR_WILDCARD) && last.ends_with(CHAR_OPTIONAL)),
+            "optional wildcard `{last}` is not supported"
+        );
+
+        let names = self.route_names(path);
+
+        let node = self.at(path);
+        node.names = names;
+        node.optional = optional;
+        node
+    }
+
+    /// Names of the params and wildcards of the pattern, in path order.
+    fn route_names(&self, path: &str) -> Vec<String> {
+        path.split(self.sep)
+            .filter_map(|seg| seg.strip_prefix([CHAR_PARAM, CHAR_WILDCARD]))
+            .map(|name| name.strip_suffix(CHAR_OPTIONAL).unwrap_or(name))
+            .map(|name| Constraint::name(name).to_owned())
+            .collect()
+    }
+
+    /// Find the node for the path without creating new nodes.
+    fn find(&self, path: &str) -> Option<usize> {
+        let mut node = self.nodes.first().unwrap().index;
+
+        let mut segs = Segments::new(path, self.sep);
+
+        while let Some(seg) = segs.next() {
+            let pat = self.segment_pattern(seg);
+
+            node = self.get_child(node, &pat)?;
+        }
+
+        Some(node)
+    }
+
     fn get(&self, index: usize) -> &Node<T> {
         &self.nodes[index]
     }
@@ -188,81 +586,349 @@ impl<T> Tree<T> {
         &mut self.nodes[index]
     }
 
-    fn search_child(&self, node: usize, pat: &str) -> Option<usize> {
-        let perfect = self.nodes.get(node).and_then(|n| {
-            match n.children.get(pat) {
-                Some(child) => return Some(child),
-                None => {
-                    if n.has_param_child {
-                        if let Some(child) = n.children.get(PAT_PARAM) {
-                            return Some(child);
-                        }
+    fn get_child(&self, node: usize, pat: &Pattern) -> Option<usize> {
+        self.nodes
+            .get(node)
+            .and_then(|n| n.children.get(pat.as_pat().as_ref()).cloned())
+    }
+
+    fn add_child(&mut self, node: usize, pat: Pattern) -> usize {
+        {
+            let node = self.get(node);
+            if let Some(child) = node.children.get(pat.as_pat().as_ref()) {
+                return *child;
+            }
+        }
+
+        let mut is_param_child = false;
+        let mut is_wildcard_child = false;
+
+        match &pat {
+            Pattern::Param(_, _) => is_param_child = true,
+            Pattern::Wildcard(_) => is_wildcard_child = true,
+            _ => {}
+        }
+
+        let pattern = pat.as_pat().into_owned();
+        let child = self.next_node(node, pat);
+
+        let node = self.get_mut(node);
+
+        node.children.insert(pattern, child);
+        if is_param_child {
+            node.has_param_child = is_param_child;
+        }
+        if is_wildcard_child {
+            node.has_wildcard_child = is_wildcard_child;
+        }
+
+        child
+    }
+
+    fn next_node(&mut self, parent: usize, pat: Pattern) -> usize {
+        let next = self.nodes.len();
+        let child = Node::new(next, parent, pat);
+        self.nodes.push(child);
+
+        next
+    }
+}
+
+/// Read access to the nodes of a tree, shared by the mutable [`Tree`] and the
+/// flattened [`Frozen`] layout so both route the same way. The root is node `0`,
+/// and nodes come after their parent.
+trait Layout {
+    fn sep(&self) -> char;
+
+    fn decode_static(&self) -> bool;
+
+    fn case_insensitive(&self) -> bool;
+
+    fn reserved(&self) -> &Reserved;
+
+    fn node_pattern(&self, node: usize) -> &Pattern;
+
+    fn parent(&self, node: usize) -> usize;
+
+    /// Child of the node under the key, as built by `Pattern::as_pat`.
+    fn child(&self, node: usize, key: &str) -> Option<usize>;
+
+    fn children(&self, node: usize) -> impl Iterator<Item = (&str, usize)>;
+
+    fn has_param_child(&self, node: usize) -> bool;
+
+    fn has_wildcard_child(&self, node: usize) -> bool;
+
+    fn names(&self, node: usize) -> &[String];
+
+    fn optional(&self, node: usize) -> bool;
+
+    fn has_data(&self, node: usize) -> bool;
+
+    /// Search the node for the path, along with its params and how it matched.
+    fn search_params(&self, path: &str) -> Result<(usize, ParamMap, MatchKind), RouteError> {
+        let (node, steps, wildcard) = self.search_node(path)?;
+        let params = self.capture_params(node, &steps);
+        let kind = MatchKind::found(wildcard, !params.is_empty());
+
+        Ok((node, params, kind))
+    }
+
+    /// Search the node for the path, along with the steps walked, so params
+    /// can be captured without parsing the path again, and whether a wildcard
+    /// took the rest of the path.
+    fn search_node<'b>(&self, path: &'b str) -> Result<(usize, Steps<'b>, bool), RouteError> {
+        // try fast path, a static route captures nothing
+        if let Some(node) = self.fast_path_search(path) {
+            return Ok((node, Vec::new(), false));
+        }
+
+        let mut steps = Vec::new();
+        let mut no_endpoint = false;
+        let mut partial = None;
+
+        if let Some(found) = self.walk(
+            0,
+            Segments::new(path, self.sep()),
+            &mut steps,
+            &mut no_endpoint,
+            &mut partial,
+        ) {
+            return Ok(found);
+        }
+
+        match partial {
+            Some((node, steps)) => Ok((node, steps, true)),
+            None if no_endpoint => Err(RouteError::NoEndpoint),
+            None => Err(RouteError::NotFound),
+        }
+    }
+
+    /// Walk the rest of the segments depth first, children tried from the most specific
+    /// like the NFA ranks captures, static over param over wildcard. A later miss falls
+    /// back to the next candidate, so the first route found prefers the earliest more
+    /// specific segment. `no_endpoint` is set when the path reaches a node without data.
+    /// The node found comes with the steps walked and whether a wildcard took its tail.
+    ///
+    /// Like the NFA ranks longer roads first, a wildcard spanning several segments
+    /// only ends the walk when no route matches segment by segment, it is kept
+    /// in `partial` meanwhile, the deepest wildcard first found winning.
+    fn walk<'b>(
+        &self,
+        node: usize,
+        mut segs: Segments<'b>,
+        steps: &mut Steps<'b>,
+        no_endpoint: &mut bool,
+        partial: &mut Option<(usize, Steps<'b>)>,
+    ) -> Option<(usize, Steps<'b>, bool)> {
+        let Some(seg) = segs.next() else {
+            let found = if self.has_data(node) {
+                Some(node)
+            } else {
+                self.optional_child(node)
+            };
+            *no_endpoint |= found.is_none();
+
+            return found.map(|node| (node, std::mem::take(steps), false));
+        };
+
+        steps.push((seg, segs.reminder()));
+        for child in self.search_children(node, seg) {
+            let found = if let Pattern::Wildcard(_) = self.node_pattern(child) {
+                // when wildcard, it takes the rest but any suffix after it
+                match self.search_wildcard(child, steps.clone()) {
+                    Some((n, _steps)) if !self.has_data(n) => {
+                        *no_endpoint = true;
+                        None
                     }
-                    if n.has_wildcard_child {
-                        if let Some(child) = n.children.get(PAT_WILDCARD) {
-                            return Some(child);
+                    // spanning several segments, a deeper route may still match
+                    Some((n, found)) if n == child && segs.clone().next().is_some() => {
+                        if partial
+                            .as_ref()
+                            .is_none_or(|(_node, best)| found.len() > best.len())
+                        {
+                            *partial = Some((n, found));
                         }
+                        None
                     }
+                    Some((n, steps)) => Some((n, steps, true)),
+                    None => None,
                 }
+            } else {
+                self.walk(child, segs.clone(), steps, no_endpoint, partial)
             };
 
-            None
-        });
+            if found.is_some() {
+                return found;
+            }
+        }
+        steps.pop();
 
-        perfect.cloned()
+        None
     }
 
-    fn search_closest_wildcard_node(&self, node: usize) -> Option<usize> {
-        let mut index = node;
+    /// Optional param child with data, matched when its segment is omitted.
+    fn optional_child(&self, node: usize) -> Option<usize> {
+        self.children(node)
+            .map(|(_key, child)| child)
+            .find(|child| self.optional(*child) && self.has_data(*child))
+    }
+
+    /// Descend through static children only, bail on the first miss or when the
+    /// node reached has no data, so the general search takes over.
+    fn fast_path_search(&self, path: &str) -> Option<usize> {
+        if self.decode_static() {
+            return None;
+        }
+
+        let mut node = 0;
+        let mut segs = Segments::new(path, self.sep());
+
+        while let Some(seg) = segs.next() {
+            node = self.child(node, Pattern::static_key(seg).as_ref())?;
+        }
 
-        loop {
-            let node = self.get(index);
-            if node.index == 0 {
-                break;
+        self.has_data(node).then_some(node)
+    }
+
+    fn search_child(&self, node: usize, seg: &str) -> Option<usize> {
+        self.search_children(node, seg).next()
+    }
+
+    /// Children matching the segment, the most specific first: the static one,
+    /// constrained params, the plain param, then the wildcard.
+    fn search_children<'s>(
+        &'s self,
+        node: usize,
+        seg: &'s str,
+    ) -> impl Iterator<Item = usize> + 's {
+        let params = self.has_param_child(node)
+            && !self
+                .reserved()
+                .contains(&self.decode_seg(seg), self.case_insensitive());
+
+        let constrained = self
+            .children(node)
+            .map(|(_key, child)| child)
+            .filter(move |child| {
+                params
+                    && match self.node_pattern(*child) {
+                        Pattern::Param(_, Some(c)) => c.is_match(seg),
+                        _ => false,
+                    }
+            });
+        let param = params.then(|| self.child(node, PAT_PARAM)).flatten();
+        let wildcard = self
+            .has_wildcard_child(node)
+            .then(|| self.child(node, PAT_WILDCARD))
+            .flatten();
+
+        self.static_child(node, seg)
+            .into_iter()
+            .chain(constrained)
+            .chain(param)
+            .chain(wildcard)
+    }
+
+    /// The segment as static children are keyed, decoded when enabled.
+    fn decode_seg<'s>(&self, seg: &'s str) -> Cow<'s, str> {
+        if self.decode_static() {
+            percent::decode(seg)
+        } else {
+            Cow::Borrowed(seg)
+        }
+    }
+
+    fn static_child(&self, node: usize, seg: &str) -> Option<usize> {
+        let decoded = self.decode_seg(seg);
+        let pat = Pattern::static_key(&decoded);
+
+        match self.child(node, pat.as_ref()) {
+            // children inserted before enabling may not be lowercased, scan for them
+            None if self.case_insensitive() => {
+                self.child(node, &pat.to_ascii_lowercase()).or_else(|| {
+                    self.children(node)
+                        .find(|(k, _v)| k.eq_ignore_ascii_case(&pat))
+                        .map(|(_k, v)| v)
+                })
             }
+            found => found,
+        }
+    }
 
-            if self.get(node.parent).has_wildcard_child {
-                let wildcard = self.get(node.parent).children.get(PAT_WILDCARD);
-                return wildcard.cloned();
-            } else {
-                index = node.parent;
+    /// Match the tail from the wildcard node, `steps` walked up to it at least.
+    ///
+    /// A wildcard followed by segments takes the longest span still leaving a matching
+    /// suffix, or the whole tail when it has an endpoint itself.
+    fn search_wildcard<'b>(
+        &self,
+        wildcard: usize,
+        mut steps: Steps<'b>,
+    ) -> Option<(usize, Steps<'b>)> {
+        steps.truncate(self.get_route_depth(wildcard));
+        if self.children(wildcard).next().is_none() {
+            return Some((wildcard, steps));
+        }
+
+        let tail = steps.last()?.1;
+        let bounds: Vec<usize> = tail.match_indices(self.sep()).map(|(i, _)| i).collect();
+
+        // suffixes from the shortest, so the wildcard spans as much as possible
+        for &end in bounds.iter().rev() {
+            let mut node = wildcard;
+            let mut matched = true;
+            let mut suffix = Vec::new();
+            let mut segs = Segments::new(&tail[end..], self.sep());
+            while let Some(seg) = segs.next() {
+                match self.search_child(node, seg) {
+                    Some(n) if !matches!(self.node_pattern(n), Pattern::Wildcard(_)) => {
+                        suffix.push((seg, segs.reminder()));
+                        node = n;
+                    }
+                    _ => {
+                        matched = false;
+                        break;
+                    }
+                }
+            }
+
+            if matched && self.has_data(node) {
+                steps.last_mut()?.1 = &tail[..end];
+                steps.extend(suffix);
+                return Some((node, steps));
             }
         }
 
-        None
+        self.has_data(wildcard).then_some((wildcard, steps))
+    }
+
+    /// Number of nodes from the root to the node, the root excluded.
+    fn get_route_depth(&self, node: usize) -> usize {
+        let mut depth = 0;
+        let mut index = node;
+        while index != 0 {
+            depth += 1;
+            index = self.parent(index);
+        }
+
+        depth
     }
 
     /// Get route path from finished node, only return path when had least one param,
-    /// otherwise return route path.
+    /// named or not, otherwise return an empty path.
     fn get_route_path(&self, node: usize) -> Vec<usize> {
         let mut path = Vec::new();
         let mut index = node;
         let mut has_param = false;
 
-        loop {
-            let node = self.get(index);
-            if node.index == 0 {
-                break;
-            }
-
-            // ignore unamed params
-            match &node.pattern {
-                Pattern::Param(p) => {
-                    if !p.is_empty() {
-                        has_param = true;
-                    }
-                }
-                Pattern::Wildcard(p) => {
-                    if !p.is_empty() {
-                        has_param = true;
-                    }
-                }
-                Pattern::Static(_) => {}
+        while index != 0 {
+            if !matches!(self.node_pattern(index), Pattern::Static(_)) {
+                has_param = true;
             }
 
             path.push(index);
 
-            index = node.parent;
+            index = self.parent(index);
         }
 
         if !has_param {
@@ -274,105 +940,234 @@ impl<T> Tree<T> {
         path
     }
 
-    fn capture_params(&self, path: &str, node: usize) -> ParamMap {
-        let mut params: ParamMap = BTreeMap::new();
-        let mut segs = Segments::new(path);
+    fn capture_params(&self, node: usize, steps: &Steps<'_>) -> ParamMap {
+        self.captures(node, steps)
+            .into_iter()
+            .map(|(index, n, v)| (index, (n.to_owned(), v.to_owned())))
+            .collect()
+    }
+
+    /// Capture params borrowed from the tree and the steps walked to the node,
+    /// along with their node, unnamed params have an empty name.
+    fn captures<'a: 'b, 'b>(
+        &'a self,
+        node: usize,
+        steps: &Steps<'b>,
+    ) -> Vec<(usize, &'b str, &'b str)> {
+        let mut params = Vec::new();
 
         let path = self.get_route_path(node);
 
-        // recapture named params
-        for index in &path {
-            if let Some(seg) = segs.next() {
-                match &self.get(*index).pattern {
-                    Pattern::Param(p) => {
-                        if !p.is_empty() {
-                            params.insert(*index, (p.to_owned(), seg.to_owned()));
-                        }
-                    }
-                    Pattern::Wildcard(p) => {
-                        if !p.is_empty() {
-                            params.insert(*index, (p.to_owned(), segs.reminder().to_owned()));
-                        }
-                    }
-                    Pattern::Static(_) => {}
-                }
-            }
+        // names on shared nodes belong to the first route, rename by the matched one,
+        // aligned to the tail since merged routes may lie under a prefix
+        let names = self.names(node);
+        let dynamics = path
+            .iter()
+            .filter(|index| !matches!(self.node_pattern(**index), Pattern::Static(_)))
+            .count();
+        let mut skipped = dynamics.saturating_sub(names.len());
+        let mut names = names.iter();
+
+        // recapture named params, node at each depth takes the step of that depth
+        for (index, (seg, reminder)) in path.iter().zip(steps) {
+            let (p, v) = match self.node_pattern(*index) {
+                Pattern::Param(p, _) => (p, *seg),
+                Pattern::Wildcard(p) => (p, *reminder),
+                Pattern::Static(_) => continue,
+            };
+
+            let p = if skipped > 0 {
+                skipped -= 1;
+                p
+            } else {
+                names.next().unwrap_or(p)
+            };
+
+            params.push((*index, p.as_str(), v));
         }
 
         params
     }
+}
 
-    fn get_child(&self, node: usize, pat: &Pattern) -> Option<usize> {
-        self.nodes
-            .get(node)
-            .and_then(|n| n.children.get(pat.as_pat()).cloned())
+impl<T> Layout for Tree<T> {
+    fn sep(&self) -> char {
+        self.sep
     }
 
-    fn add_child(&mut self, node: usize, pat: Pattern) -> usize {
-        {
-            let node = self.get(node);
-            if let Some(child) = node.children.get(pat.as_pat()) {
-                return *child;
-            }
-        }
+    fn decode_static(&self) -> bool {
+        self.decode_static
+    }
 
-        let mut is_param_child = false;
-        let mut is_wildcard_child = true;
+    fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
 
-        match &pat {
-            Pattern::Param(_) => is_param_child = true,
-            Pattern::Wildcard(_) => is_wildcard_child = true,
-            _ => {}
-        }
+    fn reserved(&self) -> &Reserved {
+        &self.reserved
+    }
 
-        let pattern = pat.as_pat().to_owned();
-        let child = self.next_node(node, pat);
+    fn node_pattern(&self, node: usize) -> &Pattern {
+        &self.get(node).pattern
+    }
 
-        let node = self.get_mut(node);
+    fn parent(&self, node: usize) -> usize {
+        self.get(node).parent
+    }
 
-        node.children.insert(pattern, child);
-        if is_param_child {
-            node.has_param_child = is_param_child;
-        }
-        if is_wildcard_child {
-            node.has_wildcard_child = is_wildcard_child;
-        }
+    fn child(&self, node: usize, key: &str) -> Option<usize> {
+        self.get(node).children.get(key).copied()
+    }
 
-        child
+    fn children(&self, node: usize) -> impl Iterator<Item = (&str, usize)> {
+        self.get(node)
+            .children
+            .iter()
+            .map(|(key, child)| (key.as_str(), *child))
     }
 
-    fn next_node(&mut self, parent: usize, pat: Pattern) -> usize {
-        let next = self.nodes.len();
-        let child = Node::new(next, parent, pat);
-        self.nodes.push(child);
+    fn has_param_child(&self, node: usize) -> bool {
+        self.get(node).has_param_child
+    }
 
-        next
+    fn has_wildcard_child(&self, node: usize) -> bool {
+        self.get(node).has_wildcard_child
+    }
+
+    fn names(&self, node: usize) -> &[String] {
+        &self.get(node).names
+    }
+
+    fn optional(&self, node: usize) -> bool {
+        self.get(node).optional
+    }
+
+    fn has_data(&self, node: usize) -> bool {
+        self.get(node).data.is_some()
     }
 }
 
+/// Immutable copy of a tree, each node field is kept in its own array and nodes
+/// are laid out breadth-first, so the children of a node are adjacent.
+#[derive(Debug, Clone)]
+pub struct Frozen<T> {
+    patterns: Vec<Pattern>,
+    parents: Vec<usize>,
+    /// Children of node `i` are `children[offsets[i]..offsets[i + 1]]`, sorted by key.
+    offsets: Vec<usize>,
+    children: Vec<(String, usize)>,
+    flags: Vec<u8>,
+    names: Vec<Vec<String>>,
+    data: Vec<Option<T>>,
+    reserved: Reserved,
+    decode_static: bool,
+    case_insensitive: bool,
+    sep: char,
+}
+
+impl<T> Frozen<T> {
+    const PARAM_CHILD: u8 = 1;
+    const WILDCARD_CHILD: u8 = 1 << 1;
+    const OPTIONAL: u8 = 1 << 2;
+
+    pub fn search(&self, path: &str) -> Result<(&T, ParamMap, MatchKind), RouteError> {
+        let (node, params, kind) = self.search_params(path)?;
+
+        self.data[node]
+            .as_ref()
+            .map(|data| (data, params, kind))
+            .ok_or(RouteError::NoEndpoint)
+    }
+
+    fn child_range(&self, node: usize) -> &[(String, usize)] {
+        &self.children[self.offsets[node]..self.offsets[node + 1]]
+    }
+}
+
+impl<T> Layout for Frozen<T> {
+    fn sep(&self) -> char {
+        self.sep
+    }
+
+    fn decode_static(&self) -> bool {
+        self.decode_static
+    }
+
+    fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    fn reserved(&self) -> &Reserved {
+        &self.reserved
+    }
+
+    fn node_pattern(&self, node: usize) -> &Pattern {
+        &self.patterns[node]
+    }
+
+    fn parent(&self, node: usize) -> usize {
+        self.parents[node]
+    }
+
+    fn child(&self, node: usize, key: &str) -> Option<usize> {
+        let children = self.child_range(node);
+
+        children
+            .binary_search_by(|(k, _child)| k.as_str().cmp(key))
+            .ok()
+            .map(|i| children[i].1)
+    }
+
+    fn children(&self, node: usize) -> impl Iterator<Item = (&str, usize)> {
+        self.child_range(node)
+            .iter()
+            .map(|(key, child)| (key.as_str(), *child))
+    }
+
+    fn has_param_child(&self, node: usize) -> bool {
+        self.flags[node] & Self::PARAM_CHILD != 0
+    }
+
+    fn has_wildcard_child(&self, node: usize) -> bool {
+        self.flags[node] & Self::WILDCARD_CHILD != 0
+    }
+
+    fn names(&self, node: usize) -> &[String] {
+        &self.names[node]
+    }
+
+    fn optional(&self, node: usize) -> bool {
+        self.flags[node] & Self::OPTIONAL != 0
+    }
+
+    fn has_data(&self, node: usize) -> bool {
+        self.data[node].is_some()
+    }
+}
+
+#[derive(Clone)]
 struct Segments<'a> {
     s: &'a str,
     pos: &'a str,
     is_last: bool,
+    sep: char,
 }
 
 impl<'a> Segments<'a> {
-    fn new(s: &'a str) -> Self {
-        // skip first `/`
-        let s = match s.strip_prefix(CHAR_PATH_SEP) {
-            Some(s) => s,
-            None => s,
-        };
+    fn new(s: &'a str, sep: char) -> Self {
+        // skip leading separators, like the NFA router
+        let s = s.trim_start_matches(sep);
 
         Segments {
             s,
             pos: s,
             is_last: false,
+            sep,
         }
     }
 
-    fn next(&mut self) -> Option<&str> {
-        match self.s.split_once(CHAR_PATH_SEP) {
+    fn next(&mut self) -> Option<&'a str> {
+        match self.s.split_once(self.sep) {
             Some((seg, s)) => {
                 self.pos = self.s;
                 self.s = s;
@@ -391,7 +1186,7 @@ impl<'a> Segments<'a> {
         }
     }
 
-    fn reminder(&self) -> &str {
+    fn reminder(&self) -> &'a str {
         self.pos
     }
 }
@@ -404,7 +1199,7 @@ mod test {
     fn test_segments() {
         let input = "/a/bc/d/efg";
 
-        let mut segs = Segments::new(input);
+        let mut segs = Segments::new(input, CHAR_PATH_SEP);
 
         // while let Some(seg) = segs.next() {
         //     println!("-> {seg}");
@@ -471,7 +1266,192 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_tree_remove() {
+        let mut tree: Tree<&'static str> = Tree::new();
+
+        tree.insert("/posts/new", "new");
+        tree.insert("/posts/:id", "id");
+        tree.insert("/posts/:id/comments", "comments");
+        tree.insert("/users/:id", "user");
+
+        assert_eq!(tree.remove("/posts/:id"), Some("id"));
+        assert_eq!(tree.remove("/posts/:id"), None);
+        assert_eq!(tree.remove("/posts/edit"), None);
+
+        assert_eq!(simple_search(&tree, "/posts/1"), None);
+        assert_eq!(simple_search(&tree, "/posts/new"), Some(&"new"));
+        assert_eq!(simple_search(&tree, "/posts/1/comments"), Some(&"comments"));
+        assert_eq!(simple_search(&tree, "/users/1"), Some(&"user"));
+
+        // still covered by another route
+        tree.insert("/posts/*rest", "rest");
+        assert_eq!(simple_search(&tree, "/posts/1"), Some(&"rest"));
+    }
+
+    #[test]
+    fn test_tree_captures() {
+        let mut tree: Tree<&'static str> = Tree::new();
+
+        tree.insert("/a/:b/c/:d/e", "deep");
+        tree.insert("/a/:b/*rest", "wildcard");
+
+        let (data, params) = tree.search_ref("/a/1/c/2/e").unwrap();
+        assert_eq!(*data, "deep");
+        assert_eq!(params, vec![("b", "1"), ("d", "2")]);
+
+        let (data, params) = tree.search_ref("/a/1/x/y").unwrap();
+        assert_eq!(*data, "wildcard");
+        assert_eq!(params, vec![("b", "1"), ("rest", "x/y")]);
+
+        // fallback to the wildcard from a node without data
+        let (data, params) = tree.search_ref("/a/1/c").unwrap();
+        assert_eq!(*data, "wildcard");
+        assert_eq!(params, vec![("b", "1"), ("rest", "c")]);
+    }
+
+    #[test]
+    fn test_tree_wildcard_flag() {
+        let mut tree: Tree<&'static str> = Tree::new();
+
+        tree.insert("/a/b", "static");
+        tree.insert("/a/:c/d", "param");
+
+        let a = tree.find("/a").unwrap();
+        assert!(!tree.get(a).has_wildcard_child);
+        assert!(tree.get(a).has_param_child);
+        let c = tree.find("/a/:c").unwrap();
+        assert!(!tree.get(c).has_wildcard_child);
+        assert!(!tree.get(c).has_param_child);
+
+        // no wildcard to fall back on
+        assert!(tree.search("/a/b/c").is_err());
+        assert!(tree.search("/a/1/e").is_err());
+
+        tree.insert("/a/*rest", "wildcard");
+        assert!(tree.get(a).has_wildcard_child);
+        assert_eq!(simple_search(&tree, "/a/1/e"), Some(&"wildcard"));
+    }
+
+    #[test]
+    fn test_tree_fast_path() {
+        let mut tree: Tree<&'static str> = Tree::new();
+
+        tree.insert("/api/v1/self/profile", "profile");
+        tree.insert("/api/v1/:user", "user");
+        tree.insert("/api/v1/:user/profile", "user_profile");
+        tree.insert("/api/*rest", "rest");
+
+        assert_eq!(
+            tree.fast_path_search("/api/v1/self/profile"),
+            tree.find("/api/v1/self/profile")
+        );
+        assert_eq!(
+            simple_search(&tree, "/api/v1/self/profile"),
+            Some(&"profile")
+        );
+
+        // static prefix without data, continue with the wildcard fallback
+        assert_eq!(tree.fast_path_search("/api/v1"), None);
+        let (data, params) = tree.search_ref("/api/v1").unwrap();
+        assert_eq!(*data, "rest");
+        assert_eq!(params, vec![("rest", "v1")]);
+
+        // static miss, continue with params
+        assert_eq!(tree.fast_path_search("/api/v1/tom/profile"), None);
+        assert_eq!(
+            simple_search(&tree, "/api/v1/tom/profile"),
+            Some(&"user_profile")
+        );
+        assert_eq!(simple_search(&tree, "/api/v2"), Some(&"rest"));
+    }
+
+    #[test]
+    fn test_tree_reserve() {
+        let mut tree = Tree::new();
+
+        tree.reserve(64);
+        assert!(tree.nodes.capacity() >= 65);
+
+        let capacity = tree.nodes.capacity();
+        tree.insert("/posts/:id/comments/:cid", 1);
+        assert_eq!(tree.nodes.capacity(), capacity);
+        assert_eq!(simple_search(&tree, "/posts/1/comments/2"), Some(&1));
+    }
+
+    #[test]
+    fn test_tree_clear() {
+        let mut tree: Tree<&'static str> = Tree::new();
+
+        tree.insert("/posts/:id/comments/:cid", "comment");
+        let capacity = tree.nodes.capacity();
+        tree.clear();
+
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(tree.nodes.capacity(), capacity);
+        assert!(tree.search("/posts/1/comments/2").is_err());
+
+        tree.insert("/users/:id", "user");
+        assert_eq!(simple_search(&tree, "/users/1"), Some(&"user"));
+    }
+
+    #[test]
+    fn test_tree_to_dot() {
+        let mut tree: Tree<&'static str> = Tree::new();
+
+        tree.insert("/posts/:id", "post");
+        tree.insert("/files/*path", "files");
+        tree.insert("/say/\"hi\"", "quote");
+
+        assert_eq!(
+            tree.to_dot(),
+            r#"digraph tree {
+    n0 [label="/", shape=circle];
+    n1 [label="posts", shape=circle];
+    n2 [label=":id", shape=doublecircle];
+    n3 [label="files", shape=circle];
+    n4 [label="*path", shape=doublecircle];
+    n5 [label="say", shape=circle];
+    n6 [label="\"hi\"", shape=doublecircle];
+    n0 -> n3 [label="files"];
+    n0 -> n1 [label="posts"];
+    n0 -> n5 [label="say"];
+    n1 -> n2 [label=":"];
+    n3 -> n4 [label="*"];
+    n5 -> n6 [label="\"hi\""];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_tree_freeze() {
+        let mut tree = Tree::new();
+
+        tree.insert("/a/b/c", 1);
+        tree.insert("/x", 2);
+        tree.insert("/a/:p", 3);
+        tree.insert("/a/*w", 4);
+
+        let frozen = tree.clone().freeze();
+
+        // breadth-first, children sorted by key
+        assert_eq!(frozen.parents, vec![0, 0, 0, 1, 1, 1, 5]);
+        assert_eq!(frozen.offsets, vec![0, 2, 5, 5, 5, 5, 6, 6]);
+        assert_eq!(frozen.child(1, "b"), Some(5));
+        assert_eq!(frozen.search("/a/b/c").unwrap().0, &1);
+        assert_eq!(frozen.search("/x").unwrap().0, &2);
+
+        for path in ["/a/b/c", "/x", "/a/q", "/a/q/r", "/a/b", "/missing"] {
+            assert_eq!(
+                tree.search(path).map(|(v, _p, _k)| *v),
+                frozen.search(path).map(|(v, _p, _k)| *v),
+                "searching `{path}`"
+            );
+        }
+    }
+
     fn simple_search<'a, T>(tree: &'a Tree<T>, path: &str) -> Option<&'a T> {
-        tree.search(path).map(|(v, _p)| v)
+        tree.search(path).ok().map(|(v, _p, _k)| v)
     }
 }
@@ -0,0 +1,44 @@
+//! Routing through an empty router does not allocate.
+//!
+//! Kept apart from the unit tests, which run on the system allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use pathrouter::{RouteError, Router};
+
+/// Count the allocations of the current thread, tests run concurrently.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn allocations<R>(f: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    drop(f());
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn empty_router() {
+    let router: Router<&str> = Router::new();
+
+    assert_eq!(allocations(|| router.route("/posts/1")), 0);
+    assert_eq!(allocations(|| router.try_route("/posts/1")), 0);
+    assert_eq!(router.try_route("/posts/1"), Err(RouteError::NotFound));
+}